@@ -0,0 +1,80 @@
+//! Opacity masks for cut-out geometry such as leaves and fences.
+use super::*;
+use std::sync::Arc;
+
+pub enum AlphaMode {
+    /// Intersections with alpha below the threshold are ignored.
+    Threshold(f64),
+    /// Intersections are kept with probability alpha, so partially
+    /// transparent texels average out over samples.
+    Stochastic,
+}
+
+/// Wraps a hittable with an opacity texture, alpha is read from its red
+/// channel at the hit's texture coordinates. Since every ray goes through
+/// `hit`, masked out parts cast no shadows either.
+pub struct AlphaMasked<H> {
+    object: H,
+    alpha: Arc<dyn Texture + Send + Sync>,
+    mode: AlphaMode,
+}
+
+impl<H: Hittable> AlphaMasked<H> {
+    pub fn new(object: H, alpha: Arc<dyn Texture + Send + Sync>, mode: AlphaMode) -> Self {
+        AlphaMasked {
+            object,
+            alpha,
+            mode,
+        }
+    }
+}
+
+impl<H: Hittable> Hittable for AlphaMasked<H> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        // Rejected hits must not leak into `rec`, callers may hold an earlier hit in it.
+        let mut candidate = HitRecord::default();
+        let mut t_min = t_min;
+        while self.object.hit(ray, t_min, t_max, &mut candidate) {
+            let alpha = self.alpha.value(candidate.u, candidate.v, &candidate.p).x;
+            let opaque = match self.mode {
+                AlphaMode::Threshold(threshold) => alpha >= threshold,
+                AlphaMode::Stochastic => random::<f64>() < alpha,
+            };
+            if opaque {
+                *rec = candidate;
+                return true;
+            }
+            // Look for the next intersection behind the masked out one.
+            t_min = candidate.t;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::quad::Quad;
+
+    #[test]
+    fn masked_out_texels_are_transparent() {
+        let mask = Arc::new(ImageTexture::new(
+            2,
+            1,
+            vec![(1, 1, 1).into(), (0, 0, 0).into()],
+        ));
+        let material = Arc::new(Lambertian::new((0.5, 0.5, 0.5).into()));
+        let quad = Quad::new(
+            (0, 0, 0).into(),
+            (1, 0, 0).into(),
+            (0, 1, 0).into(),
+            material,
+        );
+        let masked = AlphaMasked::new(quad, mask, AlphaMode::Threshold(0.5));
+        let mut rec = HitRecord::default();
+        let solid = Ray::new((0.25, 0.5, 1.0).into(), (0, 0, -1).into());
+        assert!(masked.hit(&solid, 0.001, f64::INFINITY, &mut rec));
+        let cut_out = Ray::new((0.75, 0.5, 1.0).into(), (0, 0, -1).into());
+        assert!(!masked.hit(&cut_out, 0.001, f64::INFINITY, &mut rec));
+    }
+}
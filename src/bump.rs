@@ -0,0 +1,180 @@
+//! Shading normal perturbation by tangent-space normal maps and height
+//! based bump maps, applicable to any material through `Perturbed`.
+use super::*;
+use std::sync::Arc;
+
+/// Offset in texture space used for the finite differences of bump maps.
+const BUMP_DELTA: f64 = 1.0 / 1024.0;
+
+/// Perturbs the shading normal before the wrapped material runs.
+pub trait NormalPerturbation {
+    /// New outward facing normal for the outward facing `n` at `rec`.
+    fn perturb(&self, rec: &HitRecord, n: &Point3) -> Point3;
+}
+
+/// Applies `perturbation` to `rec.normal`, keeping the orientation of the
+/// hit side. Falls back to the geometric normal if the new one would face
+/// away from the incoming ray.
+fn apply(perturbation: &impl NormalPerturbation, r_in: &Ray, rec: &mut HitRecord) {
+    if rec.dpdu.len_squared() == 0.0 || rec.dpdv.len_squared() == 0.0 {
+        return;
+    }
+    let outward = if rec.front_face {
+        rec.normal
+    } else {
+        -rec.normal
+    };
+    let n = perturbation.perturb(rec, &outward);
+    let n = if rec.front_face { n } else { -n };
+    if n.dot(r_in.direction()) < 0.0 {
+        rec.normal = n;
+    }
+}
+
+/// Tangent-space normal map, colors encode `(x, y, z) * 0.5 + 0.5` with
+/// `x` along `dpdu`, `y` along `dpdv` and `z` along the normal.
+pub struct NormalMap {
+    map: Arc<dyn Texture + Send + Sync>,
+    /// Scales the tangential components, 0 disables the map.
+    strength: f64,
+}
+
+impl NormalMap {
+    pub fn new(map: Arc<dyn Texture + Send + Sync>, strength: f64) -> Self {
+        NormalMap { map, strength }
+    }
+}
+
+impl NormalPerturbation for NormalMap {
+    fn perturb(&self, rec: &HitRecord, n: &Point3) -> Point3 {
+        let c = self.map.value(rec.u, rec.v, &rec.p);
+        let x = (2.0 * c.x - 1.0) * self.strength;
+        let y = (2.0 * c.y - 1.0) * self.strength;
+        let z = 2.0 * c.z - 1.0;
+        // Gram-Schmidt, the bitangent keeps the handedness of `dpdv`.
+        let t = (rec.dpdu - n.dot(&rec.dpdu) * *n).unit();
+        let b = n.cross(&t);
+        let b = if b.dot(&rec.dpdv) < 0.0 { -b } else { b };
+        (x * t + y * b + z * *n).unit()
+    }
+}
+
+/// Bump map, the red channel of `height` displaces the surface along its normal.
+pub struct BumpMap {
+    height: Arc<dyn Texture + Send + Sync>,
+    scale: f64,
+}
+
+impl BumpMap {
+    pub fn new(height: Arc<dyn Texture + Send + Sync>, scale: f64) -> Self {
+        BumpMap { height, scale }
+    }
+}
+
+impl NormalPerturbation for BumpMap {
+    fn perturb(&self, rec: &HitRecord, n: &Point3) -> Point3 {
+        let h = |u: f64, v: f64, p: &Point3| self.scale * self.height.value(u, v, p).x;
+        let h0 = h(rec.u, rec.v, &rec.p);
+        let hu = h(rec.u + BUMP_DELTA, rec.v, &(rec.p + BUMP_DELTA * rec.dpdu));
+        let hv = h(rec.u, rec.v + BUMP_DELTA, &(rec.p + BUMP_DELTA * rec.dpdv));
+        let dpdu = rec.dpdu + ((hu - h0) / BUMP_DELTA) * *n;
+        let dpdv = rec.dpdv + ((hv - h0) / BUMP_DELTA) * *n;
+        let bumped = dpdu.cross(&dpdv).unit();
+        if bumped.dot(n) < 0.0 {
+            -bumped
+        } else {
+            bumped
+        }
+    }
+}
+
+/// `base` shaded with the normal changed by `perturbation`, e.g.
+/// `Perturbed::new(Metal::new(albedo, 0.1), BumpMap::new(height, 0.01))`.
+pub struct Perturbed<M, P> {
+    base: M,
+    perturbation: P,
+}
+
+impl<M: Material, P: NormalPerturbation> Perturbed<M, P> {
+    pub fn new(base: M, perturbation: P) -> Self {
+        Perturbed { base, perturbation }
+    }
+}
+
+impl<M: Material, P: NormalPerturbation> Material for Perturbed<M, P> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        // `rec` stays as the integrator gave it, `eval` and `pdf` perturb
+        // their own copy and must not find the normal already tilted.
+        let mut perturbed = rec.clone();
+        apply(&self.perturbation, r_in, &mut perturbed);
+        let scatters = self
+            .base
+            .scatter(r_in, &mut perturbed, attenuation, scattered);
+        rec.specular = perturbed.specular;
+        scatters
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let mut rec = rec.clone();
+        apply(&self.perturbation, r_in, &mut rec);
+        self.base.eval(r_in, &rec, wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let mut rec = rec.clone();
+        apply(&self.perturbation, r_in, &mut rec);
+        self.base.pdf(r_in, &rec, wi)
+    }
+    fn medium(&self) -> Option<&Medium> {
+        self.base.medium()
+    }
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        self.base.emitted(r_in, rec)
+    }
+    fn emission(&self) -> Option<Color> {
+        self.base.emission()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flat_maps_keep_the_normal() {
+        let flat: Arc<dyn Texture + Send + Sync> =
+            Arc::new(SolidColor::new((0.5, 0.5, 1.0).into()));
+        let mut rec = HitRecord::default();
+        let ray = Ray::new((0, 0, 1).into(), (0.3, 0.1, -1.0).into());
+        rec.dpdu = (2, 0, 0).into();
+        rec.dpdv = (0, 3, 0).into();
+        rec.set_face_normal(&ray, &(0, 0, 1).into());
+
+        let mut mapped = rec.clone();
+        apply(&NormalMap::new(Arc::clone(&flat), 1.0), &ray, &mut mapped);
+        assert!((mapped.normal - rec.normal).len() < 1e-9);
+        let mut bumped = rec.clone();
+        apply(&BumpMap::new(flat, 1.0), &ray, &mut bumped);
+        assert!((bumped.normal - rec.normal).len() < 1e-9);
+
+        // Scattering leaves the record untouched, so the integrator's pdf
+        // after `scatter` is the one of a fresh hit.
+        let tilted: Arc<dyn Texture + Send + Sync> =
+            Arc::new(SolidColor::new((0.8, 0.3, 0.9).into()));
+        let material = Perturbed::new(
+            Lambertian::new((0.5, 0.5, 0.5).into()),
+            NormalMap::new(tilted, 1.0),
+        );
+        let wi: Point3 = (0.4, -0.2, 1.0).into();
+        let fresh = material.pdf(&ray, &rec, &wi);
+        let mut scattered_rec = rec.clone();
+        let (mut attenuation, mut scattered) = (Color::default(), Ray::default());
+        assert!(material.scatter(&ray, &mut scattered_rec, &mut attenuation, &mut scattered));
+        assert!((scattered_rec.normal - rec.normal).len() == 0.0);
+        assert!((material.pdf(&ray, &scattered_rec, &wi) - fresh).abs() < 1e-12);
+    }
+}
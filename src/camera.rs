@@ -1,17 +1,149 @@
 use super::*;
+use crate::distribution::Distribution2D;
+use std::f64::consts::PI;
 
-pub struct Camera {
+/// Where on the film, when and through which part of the lens a camera ray
+/// starts, each component uniform in `[0, 1)`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct CameraSample {
+    /// From the lower left corner of the image.
+    pub film: (f64, f64),
+    /// Within the shutter interval.
+    pub time: f64,
+    pub lens: (f64, f64),
+}
+
+impl CameraSample {
+    pub fn random(film: (f64, f64)) -> Self {
+        CameraSample {
+            film,
+            time: random(),
+            lens: (random(), random()),
+        }
+    }
+}
+
+pub trait Camera {
+    /// `None` where the film sees nothing, e.g. outside a fisheye's image circle.
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray>;
+    /// The ray with the factor its radiance is scaled by, for cameras whose
+    /// rays don't all carry the same share of the light.
+    fn generate_weighted_ray(&self, sample: &CameraSample) -> Option<(Ray, f64)> {
+        self.generate_ray(sample).map(|ray| (ray, 1.0))
+    }
+}
+
+/// Shirley and Chiu's concentric mapping of the unit square onto the unit
+/// disk, which keeps stratified lens samples stratified.
+pub fn concentric_disk((u1, u2): (f64, f64)) -> (f64, f64) {
+    let (x, y) = (2.0 * u1 - 1.0, 2.0 * u2 - 1.0);
+    if x == 0.0 && y == 0.0 {
+        return (0.0, 0.0);
+    }
+    let (r, theta) = if x.abs() > y.abs() {
+        (x, PI / 4.0 * (y / x))
+    } else {
+        (y, PI / 2.0 - PI / 4.0 * (x / y))
+    };
+    (r * theta.cos(), r * theta.sin())
+}
+
+/// Shape of the lens opening, which out of focus highlights take on.
+pub enum Aperture {
+    Circular,
+    /// Regular polygon of diaphragm blades inscribed in the lens circle,
+    /// its first corner `rotation` radians anticlockwise from the right.
+    Polygon {
+        blades: usize,
+        rotation: f64,
+    },
+    Mask(ApertureMask),
+}
+
+impl Aperture {
+    pub fn polygon(blades: usize, rotation: Degrees) -> Self {
+        Aperture::Polygon {
+            blades,
+            rotation: rotation.to_radians().into(),
+        }
+    }
+
+    /// Point on the aperture, within the unit circle, for a uniform `lens` sample.
+    pub fn sample(&self, lens: (f64, f64)) -> (f64, f64) {
+        match self {
+            Aperture::Polygon { blades, rotation } if *blades >= 3 => {
+                // A triangle between the center and two corners, all of equal area.
+                let scaled = lens.0 * *blades as f64;
+                let i = (scaled as usize).min(blades - 1);
+                let corner = |k: usize| {
+                    let angle = rotation + 2.0 * PI * k as f64 / *blades as f64;
+                    (angle.cos(), angle.sin())
+                };
+                let (a, b) = (corner(i), corner(i + 1));
+                let radial = (scaled - i as f64).sqrt();
+                let along = lens.1;
+                (
+                    radial * ((1.0 - along) * a.0 + along * b.0),
+                    radial * ((1.0 - along) * a.1 + along * b.1),
+                )
+            }
+            Aperture::Mask(mask) => mask.sample(lens),
+            _ => concentric_disk(lens),
+        }
+    }
+}
+
+/// Aperture with the transmission of an image stretched over the square
+/// around the lens circle, light where the image is bright.
+pub struct ApertureMask {
+    distribution: Distribution2D,
+}
+
+impl ApertureMask {
+    /// `transmission` is row major, starting at the top left corner.
+    pub fn new(width: usize, height: usize, transmission: &[f64]) -> Self {
+        ApertureMask {
+            distribution: Distribution2D::new(transmission, width, height),
+        }
+    }
+    /// Transmission from the luminance of a PPM image.
+    pub fn load_ppm(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let image = ImageTexture::load_ppm(path)?;
+        let (width, height) = (image.width(), image.height());
+        let transmission: Vec<f64> = (0..height)
+            .flat_map(|j| (0..width).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let u = (i as f64 + 0.5) / width as f64;
+                let v = 1.0 - (j as f64 + 0.5) / height as f64;
+                image.value(u, v, &Point3::zeroed()).luminance().max(0.0)
+            })
+            .collect();
+        Ok(ApertureMask::new(width, height, &transmission))
+    }
+    fn sample(&self, lens: (f64, f64)) -> (f64, f64) {
+        let ((u, v), _) = self.distribution.sample(lens.0, lens.1);
+        (2.0 * u - 1.0, 1.0 - 2.0 * v)
+    }
+}
+
+/// Thin-lens perspective camera focused at `focus_dist`.
+pub struct PerspectiveCamera {
     origin: Point3,
     lower_left_corner: Point3,
     horizontal: Point3,
     vertical: Point3,
     u: Point3,
     v: Point3,
-    _w: Point3,
+    w: Point3,
     lens_radius: f64,
+    aperture: Aperture,
+    /// Normal of the plane of focus, `-w` unless tilted.
+    focus_normal: Point3,
+    /// The plane of focus goes through this point on the view axis.
+    focus_point: Point3,
 }
 
-impl Camera {
+impl PerspectiveCamera {
     pub fn new(
         lookfrom: Point3,
         lookat: Point3,
@@ -35,7 +167,7 @@ impl Camera {
         let vertical = focus_dist * viewport_height * v;
         let lower_left_corner = origin - horizontal / 2.0 - vertical / 2.0 - focus_dist * w;
         let lens_radius = aperture / 2.0;
-        Camera {
+        PerspectiveCamera {
             origin,
             lower_left_corner,
             horizontal,
@@ -43,42 +175,384 @@ impl Camera {
             lens_radius,
             u,
             v,
-            _w: w,
+            w,
+            aperture: Aperture::Circular,
+            focus_normal: -w,
+            focus_point: origin - focus_dist * w,
         }
     }
+    pub fn with_aperture(mut self, aperture: Aperture) -> Self {
+        self.aperture = aperture;
+        self
+    }
+    /// Tilts the plane of focus like a tilt-shift lens, by `tilt` about the
+    /// horizontal axis, the top leaning away from the camera for positive
+    /// angles, and by `swing` about the vertical one, the right leaning away.
+    pub fn with_tilt(mut self, tilt: Degrees, swing: Degrees) -> Self {
+        self.focus_normal = (-self.w)
+            .rotate(&self.u, tilt.to_radians().into())
+            .rotate(&self.v, -f64::from(swing.to_radians()));
+        self
+    }
+    /// Slides the film in its plane by fractions of its width and height,
+    /// like a shift lens, without turning the camera.
+    pub fn with_shift(mut self, horizontal: f64, vertical: f64) -> Self {
+        self.lower_left_corner += horizontal * self.horizontal + vertical * self.vertical;
+        self
+    }
 
-    pub fn ray(&self, s: f64, t: f64) -> Ray {
-        let rd: Point3 = self.lens_radius * Point3::random_in_unit_disk();
-        let offset: Point3 = (rd.x * self.u) + rd.y * self.v;
-        Ray::new(
-            self.origin + offset,
-            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
+    pub fn focus_distance(&self) -> f64 {
+        (self.origin - self.focus_point).dot(&self.w)
+    }
+    /// Refocuses at `focus_dist` along the view axis, keeping the field of
+    /// view, shift and tilt.
+    pub fn with_focus_distance(mut self, focus_dist: f64) -> Self {
+        let scale = focus_dist / self.focus_distance();
+        self.lower_left_corner = self.origin + scale * (self.lower_left_corner - self.origin);
+        self.horizontal = scale * self.horizontal;
+        self.vertical = scale * self.vertical;
+        self.focus_point = self.origin - focus_dist * self.w;
+        self
+    }
+    /// Refocuses so that `point` is sharp, sliding a tilted plane of focus
+    /// along the view axis until it goes through `point`.
+    pub fn focus_on(self, point: &Point3) -> Self {
+        let facing = self.w.dot(&self.focus_normal);
+        if facing == 0.0 {
+            return self;
+        }
+        let distance = -(*point - self.origin).dot(&self.focus_normal) / facing;
+        if distance > 0.0 {
+            self.with_focus_distance(distance)
+        } else {
+            self
+        }
+    }
+    /// Auto-focus: refocuses on whatever the ray through the center of the
+    /// lens at `film` hits first in `world`, keeping the focus if it hits
+    /// nothing.
+    pub fn autofocus(self, world: &impl Hittable, film: (f64, f64)) -> Self {
+        let probe = Ray::new(
+            self.origin,
+            self.lower_left_corner + film.0 * self.horizontal + film.1 * self.vertical
+                - self.origin,
+        );
+        let mut rec = HitRecord::default();
+        if world.hit(&probe, 0.001, f64::INFINITY, &mut rec) {
+            self.focus_on(&rec.p)
+        } else {
+            self
+        }
+    }
+}
+
+/// Settings of a physical camera, from which the field of view, aperture
+/// and exposure of a `PerspectiveCamera` follow. One scene unit is a metre.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraSettings {
+    /// In millimetres.
+    pub focal_length: f64,
+    /// Width of the sensor in millimetres, its height follows from the
+    /// image's aspect ratio.
+    pub sensor_width: f64,
+    /// Ratio of the focal length to the aperture diameter.
+    pub f_stop: f64,
+    /// Exposure time in seconds.
+    pub shutter: f64,
+    pub iso: f64,
+}
+
+impl Default for CameraSettings {
+    /// A 50 mm lens on a full frame sensor, exposed by the sunny 16 rule.
+    fn default() -> Self {
+        CameraSettings {
+            focal_length: 50.0,
+            sensor_width: 36.0,
+            f_stop: 16.0,
+            shutter: 1.0 / 100.0,
+            iso: 100.0,
+        }
+    }
+}
+
+impl CameraSettings {
+    pub fn vfov(&self, aspect_ratio: f64) -> Degrees {
+        let sensor_height = self.sensor_width / aspect_ratio;
+        Radians::new(2.0 * (sensor_height / (2.0 * self.focal_length)).atan()).to_degrees()
+    }
+    /// Diameter of the entrance pupil in scene units.
+    pub fn aperture(&self) -> f64 {
+        self.focal_length / self.f_stop / 1000.0
+    }
+    /// Factor to scale radiance by, proportional to the light reaching the
+    /// sensor and its sensitivity: 1 for f/16, 1/100 s and ISO 100, the
+    /// sunny 16 exposure the scenes' units are meant for.
+    pub fn exposure(&self) -> f64 {
+        let reference = CameraSettings::default();
+        let relative = |s: &CameraSettings| s.shutter * s.iso / (s.f_stop * s.f_stop);
+        relative(self) / relative(&reference)
+    }
+    pub fn camera(
+        &self,
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Point3,
+        aspect_ratio: f64,
+        focus_dist: f64,
+    ) -> PerspectiveCamera {
+        PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            self.vfov(aspect_ratio),
+            aspect_ratio,
+            self.aperture(),
+            focus_dist,
         )
     }
 }
 
+impl Camera for PerspectiveCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let (s, t) = sample.film;
+        let through_center =
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin;
+        // Where the ray through the center of the lens meets the plane of
+        // focus, sharp for every point on the lens.
+        let facing = through_center.dot(&self.focus_normal);
+        let focus = if facing > 0.0 {
+            (self.focus_point - self.origin).dot(&self.focus_normal) / facing * through_center
+        } else {
+            through_center
+        };
+        let (dx, dy) = self.aperture.sample(sample.lens);
+        let offset: Point3 = self.lens_radius * (dx * self.u + dy * self.v);
+        let ray = Ray::new(self.origin + offset, focus - offset);
+        Some(ray.with_time(sample.time))
+    }
+}
+
+/// Parallel projection for technical and isometric views, looking along
+/// `lookat - lookfrom` at a film `view_height` world units tall.
+pub struct OrthographicCamera {
+    lower_left_corner: Point3,
+    horizontal: Point3,
+    vertical: Point3,
+    direction: Point3,
+}
+
+impl OrthographicCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Point3,
+        view_height: f64,
+        aspect_ratio: f64,
+    ) -> Self {
+        let w = (lookfrom - lookat).unit();
+        let u = vup.cross(&w).unit();
+        let v = w.cross(&u);
+        let horizontal = aspect_ratio * view_height * u;
+        let vertical = view_height * v;
+        OrthographicCamera {
+            lower_left_corner: lookfrom - horizontal / 2.0 - vertical / 2.0,
+            horizontal,
+            vertical,
+            direction: -w,
+        }
+    }
+}
+
+impl Camera for OrthographicCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let (s, t) = sample.film;
+        let ray = Ray::new(
+            self.lower_left_corner + s * self.horizontal + t * self.vertical,
+            self.direction,
+        );
+        Some(ray.with_time(sample.time))
+    }
+}
+
 pub struct Degrees(f64);
 impl Degrees {
     pub fn new(val: f64) -> Self {
         Degrees(val)
     }
-    fn to_radians(&self) -> Radians {
+    pub fn to_radians(&self) -> Radians {
         Radians(self.0 * std::f64::consts::PI / 180.0)
     }
 }
-impl Into<f64> for Degrees {
-    fn into(self) -> f64 {
-        self.0
+impl From<Degrees> for f64 {
+    fn from(val: Degrees) -> f64 {
+        val.0
     }
 }
 pub struct Radians(f64);
 impl Radians {
-    fn to_degrees(&self) -> Radians {
-        todo!()
+    pub fn new(val: f64) -> Self {
+        Radians(val)
+    }
+    pub fn to_degrees(&self) -> Degrees {
+        Degrees(self.0 * 180.0 / std::f64::consts::PI)
+    }
+}
+impl From<Radians> for f64 {
+    fn from(val: Radians) -> f64 {
+        val.0
     }
 }
-impl Into<f64> for Radians {
-    fn into(self) -> f64 {
-        self.0
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sphere::Sphere;
+    use std::sync::Arc;
+
+    #[test]
+    fn perspective_and_orthographic_rays() {
+        let perspective = PerspectiveCamera::new(
+            (0, 0, 5).into(),
+            Point3::zeroed(),
+            (0, 1, 0).into(),
+            Degrees::new(90.0),
+            2.0,
+            0.0,
+            5.0,
+        );
+        let mut sample = CameraSample {
+            film: (0.5, 0.5),
+            time: 0.25,
+            lens: (0.3, 0.8),
+        };
+        let ray = perspective.generate_ray(&sample).unwrap();
+        assert!((ray.direction().unit().z + 1.0).abs() < 1e-12);
+        assert_eq!(ray.time(), 0.25);
+        // 90° vertically and twice as wide.
+        sample.film = (1.0, 1.0);
+        let corner = perspective.generate_ray(&sample).unwrap().at(1.0);
+        assert!((corner.x - 10.0).abs() < 1e-9 && (corner.y - 5.0).abs() < 1e-9);
+
+        let ortho = OrthographicCamera::new(
+            (0, 0, 5).into(),
+            Point3::zeroed(),
+            (0, 1, 0).into(),
+            2.0,
+            2.0,
+        );
+        let ray = ortho.generate_ray(&sample).unwrap();
+        assert!((ray.origin().x - 2.0).abs() < 1e-12 && (ray.origin().y - 1.0).abs() < 1e-12);
+        assert!((ray.direction().z + 1.0).abs() < 1e-12);
+
+        for (u1, u2) in [(0.0, 0.0), (0.9, 0.1), (0.5, 1.0)] {
+            let (x, y) = concentric_disk((u1, u2));
+            assert!(x * x + y * y <= 1.0 + 1e-12);
+        }
+    }
+
+    #[test]
+    fn apertures_and_tilted_focus() {
+        let hexagon = Aperture::polygon(6, Degrees::new(0.0));
+        // With a corner to the right the top is an edge, at the apothem.
+        let apothem = 30f64.to_radians().cos();
+        for k in 0..200 {
+            let lens = ((k as f64 + 0.5) / 200.0, (k * 37 % 200) as f64 / 200.0);
+            let (x, y) = hexagon.sample(lens);
+            let radius = (x * x + y * y).sqrt();
+            assert!(radius <= 1.0 + 1e-12);
+            assert!(y <= apothem + 1e-12);
+        }
+        // Only the lower right quarter of the mask lets light through.
+        let mask = Aperture::Mask(ApertureMask::new(2, 2, &[0.0, 0.0, 0.0, 1.0]));
+        for lens in [(0.1, 0.2), (0.7, 0.9), (0.4, 0.5)] {
+            let (x, y) = mask.sample(lens);
+            assert!(x >= 0.0 && y <= 0.0);
+        }
+
+        let tilted = PerspectiveCamera::new(
+            (0, 0, 0).into(),
+            (0, 0, -1).into(),
+            (0, 1, 0).into(),
+            Degrees::new(40.0),
+            1.0,
+            0.5,
+            4.0,
+        )
+        .with_aperture(Aperture::polygon(5, Degrees::new(0.0)))
+        .with_tilt(Degrees::new(-60.0), Degrees::new(0.0));
+        // Rays through any point of the lens for one film position cross at a
+        // single point, nearer than 4 at the bottom of the image.
+        let focus = |film: (f64, f64)| {
+            let points: Vec<Point3> = [(0.1, 0.2), (0.8, 0.6), (0.5, 0.95)]
+                .iter()
+                .map(|lens| {
+                    let sample = CameraSample {
+                        film,
+                        lens: *lens,
+                        ..Default::default()
+                    };
+                    tilted.generate_ray(&sample).unwrap().at(1.0)
+                })
+                .collect();
+            for p in &points {
+                assert!((*p - points[0]).len() < 1e-9);
+            }
+            -points[0].z
+        };
+        assert!((focus((0.5, 0.5)) - 4.0).abs() < 1e-9);
+        assert!(focus((0.5, 0.0)) < 4.0 && focus((0.5, 1.0)) > 4.0);
+    }
+
+    #[test]
+    fn autofocus_and_physical_settings() {
+        let settings = CameraSettings {
+            f_stop: 2.8,
+            ..Default::default()
+        };
+        // 24 mm high behind a 50 mm lens.
+        let vfov: f64 = settings.vfov(1.5).into();
+        assert!((vfov - 2.0 * (12f64 / 50.0).atan().to_degrees()).abs() < 1e-9);
+        assert!((settings.aperture() - 0.05 / 2.8).abs() < 1e-12);
+        assert!((CameraSettings::default().exposure() - 1.0).abs() < 1e-12);
+        // Two stops less shutter, two stops more aperture.
+        let faster = CameraSettings {
+            f_stop: 8.0,
+            shutter: 1.0 / 400.0,
+            ..Default::default()
+        };
+        assert!((faster.exposure() - 1.0).abs() < 1e-12);
+
+        let world = vec![Sphere::new(
+            (0, 0, -7).into(),
+            1.0,
+            Arc::new(Lambertian::new((0.5, 0.5, 0.5).into())),
+        )];
+        let camera = settings
+            .camera(
+                Point3::zeroed(),
+                (0, 0, -1).into(),
+                (0, 1, 0).into(),
+                1.5,
+                1.0,
+            )
+            .autofocus(&world, (0.5, 0.5));
+        assert!((camera.focus_distance() - 6.0).abs() < 1e-9);
+        let sample = CameraSample {
+            film: (0.5, 0.5),
+            ..Default::default()
+        };
+        let focused = camera.generate_ray(&sample).unwrap();
+        assert!((focused.at(1.0).z + 6.0).abs() < 1e-9);
+        // Missing everything keeps the focus.
+        let camera = camera.autofocus(&world, (0.0, 0.0));
+        assert!((camera.focus_distance() - 6.0).abs() < 1e-9);
+        let camera = camera.focus_on(&(1, 2, -3).into());
+        assert!((camera.focus_distance() - 3.0).abs() < 1e-9);
+        // A tilted plane is moved to go through the point, not just its depth.
+        let point: Point3 = (0, 1, -4).into();
+        let tilted = camera
+            .with_tilt(Degrees::new(20.0), Degrees::new(10.0))
+            .focus_on(&point);
+        assert!((point - tilted.focus_point).dot(&tilted.focus_normal).abs() < 1e-9);
     }
 }
@@ -0,0 +1,163 @@
+//! Smooth dielectric coating over an arbitrary base material,
+//! e.g. car paint or varnished wood.
+use super::*;
+
+pub struct Coated<M> {
+    base: M,
+    ior: f64,
+    /// Absorption coefficient of the coat per unit length.
+    absorption: Color,
+    thickness: f64,
+}
+
+impl<M: Material> Coated<M> {
+    pub fn new(base: M, ior: f64) -> Self {
+        Coated {
+            base,
+            ior,
+            absorption: Color::zeroed(),
+            thickness: 0.0,
+        }
+    }
+    /// Tinted coat, light is attenuated by `exp(-absorption * distance)` inside it.
+    pub fn with_absorption(mut self, absorption: Color, thickness: f64) -> Self {
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    /// Solid angle inside the coat per solid angle outside, refraction
+    /// squeezes the hemisphere into the cone below the critical angle.
+    fn jacobian(&self, cos_outside: f64, cos_inside: f64) -> f64 {
+        cos_outside / (self.ior * self.ior * cos_inside)
+    }
+
+    /// Beer-Lambert transmittance of a path going down and back up through the coat.
+    fn transmittance(&self, cos_down: f64, cos_up: f64) -> Color {
+        let distance = self.thickness * (1.0 / cos_down.abs() + 1.0 / cos_up.abs());
+        Color::new(
+            (-self.absorption.x * distance).exp(),
+            (-self.absorption.y * distance).exp(),
+            (-self.absorption.z * distance).exp(),
+        )
+    }
+}
+
+impl<M: Material> Material for Coated<M> {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let unit_direction = r_in.direction().unit();
+        let cos_o = (-unit_direction).dot(&rec.normal);
+        if cos_o <= 0.0 {
+            return false;
+        }
+        if random::<f64>() < fresnel_dielectric(cos_o, self.ior) {
+            *scattered = Ray::new(rec.p, unit_direction.reflect(&rec.normal));
+            *attenuation = (1, 1, 1).into();
+            rec.specular = true;
+            return true;
+        }
+
+        // Refract into the coat and let the base scatter the light back up.
+        let down = unit_direction.refract(&rec.normal, 1.0 / self.ior);
+        let mut base_attenuation = Color::zeroed();
+        let mut up = Ray::default();
+        if !self
+            .base
+            .scatter(&Ray::new(rec.p, down), rec, &mut base_attenuation, &mut up)
+        {
+            return false;
+        }
+        let up_dir = up.direction().unit();
+        let cos_up = up_dir.dot(&rec.normal);
+        let exit_transmission = 1.0 - fresnel_dielectric(cos_up, 1.0 / self.ior);
+        if cos_up <= 0.0 || exit_transmission <= 0.0 {
+            return false;
+        }
+        // Light reflected back at the coat's inner side is dropped.
+        *scattered = Ray::new(rec.p, up_dir.refract(&-rec.normal, self.ior));
+        *attenuation = exit_transmission
+            * base_attenuation
+            * self.transmittance(down.dot(&rec.normal), cos_up);
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let unit_direction = r_in.direction().unit();
+        let wi = wi.unit();
+        let cos_o = (-unit_direction).dot(&rec.normal);
+        let cos_i = wi.dot(&rec.normal);
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return Color::zeroed();
+        }
+        let down = unit_direction.refract(&rec.normal, 1.0 / self.ior);
+        let up = -(-wi).refract(&rec.normal, 1.0 / self.ior);
+        let cos_up = up.dot(&rec.normal);
+        let transmission = (1.0 - fresnel_dielectric(cos_o, self.ior))
+            * (1.0 - fresnel_dielectric(cos_i, self.ior));
+        // The radiance scaling by the squared IOR on the way in and out
+        // cancels, leaving the narrower solid angle inside.
+        (transmission * self.jacobian(cos_i, cos_up))
+            * self.base.eval(&Ray::new(rec.p, down), rec, &up)
+            * self.transmittance(down.dot(&rec.normal), cos_up)
+    }
+    /// Density of the light refracted through the coat, the coat's mirror
+    /// reflection is flagged as specular by `scatter`.
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let unit_direction = r_in.direction().unit();
+        let wi = wi.unit();
+        let cos_o = (-unit_direction).dot(&rec.normal);
+        let cos_i = wi.dot(&rec.normal);
+        if cos_o <= 0.0 || cos_i <= 0.0 {
+            return 0.0;
+        }
+        let down = unit_direction.refract(&rec.normal, 1.0 / self.ior);
+        let up = -(-wi).refract(&rec.normal, 1.0 / self.ior);
+        (1.0 - fresnel_dielectric(cos_o, self.ior))
+            * self.jacobian(cos_i, up.dot(&rec.normal))
+            * self.base.pdf(&Ray::new(rec.p, down), rec, &up)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn black_base_only_shows_coat_reflection() {
+        let coated = Coated::new(Lambertian::new(Color::zeroed()), 1.5);
+        let r_in = Ray::new((0, 1, 0).into(), (0, -1, 0).into());
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        let n = 100_000;
+        let mut sum = 0.0;
+        for _ in 0..n {
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            if coated.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered) {
+                sum += attenuation.x;
+            }
+        }
+        assert!((sum / n as f64 - 0.04).abs() < 0.005);
+
+        // A diffuse base only scatters into the cone that refracts out,
+        // sin^2 of the critical angle is 1 / 1.5^2 of the cosine lobe.
+        let coated = Coated::new(Lambertian::new((1, 1, 1).into()), 1.5);
+        let n = 200_000;
+        let mut integral = 0.0;
+        for _ in 0..n {
+            let wi = Point3::random_in_hemisphere(&rec.normal);
+            integral += coated.pdf(&r_in, &rec, &wi) * 2.0 * std::f64::consts::PI;
+        }
+        assert!((integral / n as f64 - 0.96 / 2.25).abs() < 0.01);
+
+        let tinted = Coated::new(Lambertian::new((1, 1, 1).into()), 1.5)
+            .with_absorption((10, 10, 10).into(), 1.0);
+        let up = rec.normal;
+        assert!(tinted.eval(&r_in, &rec, &up).x < 1e-6 * coated.eval(&r_in, &rec, &up).x);
+    }
+}
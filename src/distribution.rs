@@ -0,0 +1,197 @@
+//! Piecewise-constant and discrete distributions for importance sampling
+//! tabulated functions such as environment maps, or picking lights.
+
+/// Distribution over `[0, 1)` proportional to a step function.
+pub struct Distribution1D {
+    func: Vec<f64>,
+    /// `func.len() + 1` entries, from 0 to 1.
+    cdf: Vec<f64>,
+    integral: f64,
+}
+
+impl Distribution1D {
+    /// `func` must be non-negative. If it's zero everywhere sampling is uniform.
+    pub fn new(func: Vec<f64>) -> Self {
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for (i, f) in func.iter().enumerate() {
+            cdf.push(cdf[i] + f / n as f64);
+        }
+        let integral = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate().skip(1) {
+            *c = if integral == 0.0 {
+                i as f64 / n as f64
+            } else {
+                *c / integral
+            };
+        }
+        Distribution1D {
+            func,
+            cdf,
+            integral,
+        }
+    }
+    pub fn integral(&self) -> f64 {
+        self.integral
+    }
+    pub fn len(&self) -> usize {
+        self.func.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.func.is_empty()
+    }
+
+    /// Maps the uniform `u` to `(x, pdf, segment)`.
+    pub fn sample(&self, u: f64) -> (f64, f64, usize) {
+        // Last segment whose cdf start is <= u.
+        let offset = self.cdf.partition_point(|c| *c <= u).clamp(1, self.len()) - 1;
+        let width = self.cdf[offset + 1] - self.cdf[offset];
+        let du = if width > 0.0 {
+            (u - self.cdf[offset]) / width
+        } else {
+            0.0
+        };
+        let x = (offset as f64 + du) / self.len() as f64;
+        (x, self.pdf(x), offset)
+    }
+    /// Density at `x` in `[0, 1)`.
+    pub fn pdf(&self, x: f64) -> f64 {
+        let offset = ((x * self.len() as f64) as usize).min(self.len() - 1);
+        if self.integral == 0.0 {
+            1.0
+        } else {
+            self.func[offset] / self.integral
+        }
+    }
+}
+
+/// Distribution over `[0, 1)²` proportional to a step function given row by row.
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    pub fn new(func: &[f64], width: usize, height: usize) -> Self {
+        assert_eq!(func.len(), width * height);
+        let conditional: Vec<_> = func
+            .chunks(width)
+            .map(|row| Distribution1D::new(row.to_vec()))
+            .collect();
+        let marginal = Distribution1D::new(conditional.iter().map(|c| c.integral()).collect());
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Maps uniform samples to `((u, v), pdf)`, `v` selecting the row.
+    pub fn sample(&self, u1: f64, u2: f64) -> ((f64, f64), f64) {
+        let (v, pdf_v, row) = self.marginal.sample(u2);
+        let (u, pdf_u, _) = self.conditional[row].sample(u1);
+        ((u, v), pdf_u * pdf_v)
+    }
+    pub fn pdf(&self, u: f64, v: f64) -> f64 {
+        let rows = self.conditional.len();
+        let row = ((v * rows as f64) as usize).min(rows - 1);
+        self.marginal.pdf(v) * self.conditional[row].pdf(u)
+    }
+}
+
+/// Constant time sampling of a discrete distribution (Vose's alias method).
+pub struct AliasTable {
+    /// Probability of keeping each bin rather than jumping to its alias.
+    keep: Vec<f64>,
+    alias: Vec<usize>,
+    pmf: Vec<f64>,
+}
+
+impl AliasTable {
+    /// `weights` must be non-negative. If they're all zero the table is uniform.
+    pub fn new(weights: &[f64]) -> Self {
+        let n = weights.len();
+        let total: f64 = weights.iter().sum();
+        let pmf: Vec<f64> = if total > 0.0 {
+            weights.iter().map(|w| w / total).collect()
+        } else {
+            vec![1.0 / n as f64; n]
+        };
+        let mut keep: Vec<f64> = pmf.iter().map(|p| p * n as f64).collect();
+        let mut alias: Vec<usize> = (0..n).collect();
+        let (mut small, mut large): (Vec<usize>, Vec<usize>) = (0..n).partition(|i| keep[*i] < 1.0);
+        while !small.is_empty() && !large.is_empty() {
+            let (s, l) = (small.pop().unwrap(), large[large.len() - 1]);
+            alias[s] = l;
+            keep[l] -= 1.0 - keep[s];
+            if keep[l] < 1.0 {
+                large.pop();
+                small.push(l);
+            }
+        }
+        // Leftovers are 1 up to rounding.
+        for i in small.into_iter().chain(large) {
+            keep[i] = 1.0;
+        }
+        AliasTable { keep, alias, pmf }
+    }
+    pub fn len(&self) -> usize {
+        self.pmf.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.pmf.is_empty()
+    }
+    /// Maps the uniform `u` to an index and its probability.
+    pub fn sample(&self, u: f64) -> (usize, f64) {
+        let scaled = u * self.len() as f64;
+        let bin = (scaled as usize).min(self.len() - 1);
+        let index = if scaled - (bin as f64) < self.keep[bin] {
+            bin
+        } else {
+            self.alias[bin]
+        };
+        (index, self.pmf[index])
+    }
+    pub fn pmf(&self, index: usize) -> f64 {
+        self.pmf[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_follow_the_function() {
+        let dist = Distribution1D::new(vec![0.0, 1.0, 3.0, 0.0]);
+        assert!((dist.integral() - 1.0).abs() < 1e-12);
+        let (x, pdf, segment) = dist.sample(0.5);
+        assert_eq!(segment, 2);
+        assert!((pdf - 3.0).abs() < 1e-12);
+        assert!((x - (0.5 + 1.0 / 3.0 * 0.25)).abs() < 1e-12);
+        assert_eq!(dist.sample(0.1).2, 1);
+
+        let dist = Distribution2D::new(&[0.0, 0.0, 0.0, 4.0], 2, 2);
+        let ((u, v), pdf) = dist.sample(0.3, 0.7);
+        assert!(u >= 0.5 && v >= 0.5);
+        assert!((pdf - 4.0).abs() < 1e-12);
+        assert!((dist.pdf(u, v) - pdf).abs() < 1e-12);
+        assert_eq!(dist.pdf(0.2, 0.2), 0.0);
+    }
+
+    #[test]
+    fn alias_table_matches_weights() {
+        let table = AliasTable::new(&[1.0, 0.0, 3.0, 4.0]);
+        let mut counts = [0usize; 4];
+        let n = 8000;
+        for i in 0..n {
+            let (index, pmf) = table.sample((i as f64 + 0.5) / n as f64);
+            assert_eq!(pmf, table.pmf(index));
+            counts[index] += 1;
+        }
+        assert_eq!(counts[1], 0);
+        for (count, expected) in counts.iter().zip(&[0.125, 0.0, 0.375, 0.5]) {
+            assert!((*count as f64 / n as f64 - expected).abs() < 1e-3);
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Light arriving from infinitely far away, seen by rays leaving the scene.
+use super::*;
+use crate::camera::Degrees;
+use crate::distribution::Distribution2D;
+use std::f64::consts::PI;
+use std::io;
+
+pub trait Environment {
+    /// Radiance arriving from the unit `direction`.
+    fn radiance(&self, direction: &Point3) -> Color;
+    /// Unit direction picked for next-event estimation and its solid angle
+    /// density, `None` if the environment isn't importance sampled.
+    fn sample(&self) -> Option<(Point3, f64)> {
+        None
+    }
+    /// Solid angle density with which `sample` picks `direction`.
+    fn pdf(&self, _direction: &Point3) -> f64 {
+        0.0
+    }
+}
+
+/// Vertical blend from `horizon` straight down to `zenith` straight up.
+pub struct Gradient {
+    horizon: Color,
+    zenith: Color,
+}
+
+impl Gradient {
+    pub fn new(horizon: Color, zenith: Color) -> Self {
+        Gradient { horizon, zenith }
+    }
+}
+
+impl Default for Gradient {
+    /// White to light blue.
+    fn default() -> Self {
+        Gradient::new((1, 1, 1).into(), (0.5, 0.7, 1.0).into())
+    }
+}
+
+impl Environment for Gradient {
+    fn radiance(&self, direction: &Point3) -> Color {
+        let t = 0.5 * (direction.y + 1.0);
+        (1.0 - t) * self.horizon + t * self.zenith
+    }
+}
+
+/// Equirectangular (latitude-longitude) map, the top row looks straight up
+/// and the center column down the +x axis, columns to its right turn
+/// towards +z.
+pub struct EnvironmentMap {
+    width: usize,
+    height: usize,
+    data: Vec<Color>,
+    /// Around the y axis, in radians.
+    rotation: f64,
+    intensity: f64,
+    distribution: Distribution2D,
+}
+
+impl EnvironmentMap {
+    /// `data` is row major, starting at the top left corner, and not empty.
+    pub fn new(width: usize, height: usize, data: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "empty environment map");
+        assert_eq!(data.len(), width * height);
+        // Rows near the poles cover less solid angle.
+        let func: Vec<f64> = data
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                let theta = PI * ((i / width) as f64 + 0.5) / height as f64;
+                c.luminance().max(0.0) * theta.sin()
+            })
+            .collect();
+        let distribution = Distribution2D::new(&func, width, height);
+        EnvironmentMap {
+            width,
+            height,
+            data,
+            rotation: 0.0,
+            intensity: 1.0,
+            distribution,
+        }
+    }
+    /// Reads Radiance `.hdr` or OpenEXR `.exr` files by extension.
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase());
+        match extension.as_deref() {
+            Some("hdr") | Some("pic") => {
+                let (width, height, data) = parse_hdr(&std::fs::read(path)?)?;
+                Ok(EnvironmentMap::new(width, height, data))
+            }
+            Some("exr") => load_exr(path),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "environment maps must be .hdr or .exr files",
+            )),
+        }
+    }
+    pub fn with_rotation(mut self, rotation: Degrees) -> Self {
+        self.rotation = rotation.to_radians().into();
+        self
+    }
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Map coordinates in `[0, 1)²` for a unit direction, `v` going down.
+    fn direction_to_uv(&self, d: &Point3) -> (f64, f64) {
+        let theta = clamp(d.y, -1.0, 1.0).acos();
+        let phi = d.z.atan2(d.x) - self.rotation;
+        let u = (phi / (2.0 * PI) + 0.5).rem_euclid(1.0);
+        (u, theta / PI)
+    }
+    fn uv_to_direction(&self, u: f64, v: f64) -> Point3 {
+        let theta = PI * v;
+        let phi = 2.0 * PI * (u - 0.5) + self.rotation;
+        let sin_theta = theta.sin();
+        Point3::new(sin_theta * phi.cos(), theta.cos(), sin_theta * phi.sin())
+    }
+    fn texel(&self, u: f64, v: f64) -> Color {
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.data[j * self.width + i]
+    }
+}
+
+impl Environment for EnvironmentMap {
+    fn radiance(&self, direction: &Point3) -> Color {
+        let (u, v) = self.direction_to_uv(direction);
+        self.intensity * self.texel(u, v)
+    }
+    fn sample(&self) -> Option<(Point3, f64)> {
+        let ((u, v), pdf) = self.distribution.sample(random(), random());
+        let sin_theta = (PI * v).sin();
+        if pdf == 0.0 || sin_theta == 0.0 {
+            return None;
+        }
+        // Jacobian of the latitude-longitude mapping.
+        Some((
+            self.uv_to_direction(u, v),
+            pdf / (2.0 * PI * PI * sin_theta),
+        ))
+    }
+    fn pdf(&self, direction: &Point3) -> f64 {
+        let (u, v) = self.direction_to_uv(direction);
+        let sin_theta = (PI * v).sin();
+        if sin_theta == 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(u, v) / (2.0 * PI * PI * sin_theta)
+    }
+}
+
+/// Power heuristic weight of a sample from the strategy with density `a`
+/// against the one with density `b`.
+pub fn power_heuristic(a: f64, b: f64) -> f64 {
+    if a == 0.0 {
+        return 0.0;
+    }
+    a * a / (a * a + b * b)
+}
+
+/// Next-event estimation of the environment at `rec`, weighted by multiple
+/// importance sampling against the material's own sampling. Rays escaping
+/// after `scatter` must be weighted with `power_heuristic(bsdf_pdf, env_pdf)`.
+pub fn environment_lighting(
+    environment: &dyn Environment,
+    world: &impl Hittable,
+    r_in: &Ray,
+    rec: &HitRecord,
+    material: &dyn Material,
+) -> Color {
+    let (wi, pdf) = match environment.sample() {
+        Some(sample) => sample,
+        None => return Color::zeroed(),
+    };
+    let f = material.eval(r_in, rec, &wi);
+    if f.x == 0.0 && f.y == 0.0 && f.z == 0.0 {
+        return Color::zeroed();
+    }
+    let mut shadow_rec = HitRecord::default();
+    if world.hit(&Ray::new(rec.p, wi), 0.001, f64::INFINITY, &mut shadow_rec) {
+        return Color::zeroed();
+    }
+    let weight = power_heuristic(pdf, material.pdf(r_in, rec, &wi));
+    (weight / pdf) * f * environment.radiance(&wi)
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+/// Parses a Radiance RGBE picture, flat or with run-length encoded scanlines.
+fn parse_hdr(bytes: &[u8]) -> io::Result<(usize, usize, Vec<Color>)> {
+    let mut pos = 0;
+    let mut line = || {
+        let start = pos.min(bytes.len());
+        while pos < bytes.len() && bytes[pos] != b'\n' {
+            pos += 1;
+        }
+        pos += 1;
+        String::from_utf8_lossy(&bytes[start..(pos - 1).min(bytes.len()).max(start)]).into_owned()
+    };
+    if !line().starts_with("#?") {
+        return Err(invalid("not a Radiance HDR file"));
+    }
+    loop {
+        let header = line();
+        if header.is_empty() {
+            break;
+        }
+        if header.starts_with("FORMAT=") && header != "FORMAT=32-bit_rle_rgbe" {
+            return Err(invalid("only RGBE Radiance pictures are supported"));
+        }
+    }
+    let resolution = line();
+    let words: Vec<&str> = resolution.split_whitespace().collect();
+    let (height, width) = match words.as_slice() {
+        ["-Y", h, "+X", w] => (h.parse::<usize>(), w.parse::<usize>()),
+        _ => return Err(invalid("only -Y h +X w orientation is supported")),
+    };
+    let (height, width) = match (height, width) {
+        (Ok(h), Ok(w)) if h > 0 && w > 0 => (h, w),
+        _ => return Err(invalid("bad HDR resolution")),
+    };
+    let mut pos = pos.min(bytes.len());
+    let mut data = Vec::with_capacity(width * height);
+    let mut scanline = vec![[0u8; 4]; width];
+    let next = |pos: &mut usize| {
+        let b = *bytes
+            .get(*pos)
+            .ok_or_else(|| invalid("HDR data is truncated"))?;
+        *pos += 1;
+        Ok::<u8, io::Error>(b)
+    };
+    for _ in 0..height {
+        let rle = (8..0x8000).contains(&width)
+            && bytes.len() >= pos + 4
+            && bytes[pos] == 2
+            && bytes[pos + 1] == 2
+            && bytes[pos + 2] & 0x80 == 0;
+        if rle {
+            if ((bytes[pos + 2] as usize) << 8 | bytes[pos + 3] as usize) != width {
+                return Err(invalid("HDR scanline width mismatch"));
+            }
+            pos += 4;
+            for channel in 0..4 {
+                let mut x = 0;
+                while x < width {
+                    let count = next(&mut pos)? as usize;
+                    let (run, count) = if count > 128 {
+                        (true, count - 128)
+                    } else {
+                        (false, count)
+                    };
+                    if count == 0 || x + count > width {
+                        return Err(invalid("bad HDR run length"));
+                    }
+                    let value = if run { next(&mut pos)? } else { 0 };
+                    for texel in &mut scanline[x..x + count] {
+                        texel[channel] = if run { value } else { next(&mut pos)? };
+                    }
+                    x += count;
+                }
+            }
+        } else {
+            for texel in &mut scanline {
+                for c in texel.iter_mut() {
+                    *c = next(&mut pos)?;
+                }
+            }
+        }
+        data.extend(scanline.iter().map(|[r, g, b, e]| {
+            if *e == 0 {
+                return Color::zeroed();
+            }
+            let f = 2f64.powi(*e as i32 - (128 + 8));
+            Color::new(*r as f64 * f, *g as f64 * f, *b as f64 * f)
+        }));
+    }
+    Ok((width, height, data))
+}
+
+fn load_exr(path: &std::path::Path) -> io::Result<EnvironmentMap> {
+    use exr::prelude::*;
+    let image = read_first_rgba_layer_from_file(
+        path,
+        |resolution, _| {
+            (
+                resolution.width(),
+                vec![Color::zeroed(); resolution.width() * resolution.height()],
+            )
+        },
+        |(width, data), position, (r, g, b, _): (f32, f32, f32, f32)| {
+            data[position.y() * *width + position.x()] = Color::new(r as f64, g as f64, b as f64);
+        },
+    )
+    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let (width, data) = image.layer_data.channel_data.pixels;
+    if width == 0 || data.is_empty() {
+        return Err(invalid("EXR image is empty"));
+    }
+    let height = data.len() / width;
+    Ok(EnvironmentMap::new(width, height, data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flat_and_rle_hdr() {
+        let mut bytes = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 8\n".to_vec();
+        // Flat scanline, 1.0 is (128, 128, 128, 129).
+        for _ in 0..8 {
+            bytes.extend_from_slice(&[128, 128, 128, 129]);
+        }
+        // Run-length encoded scanline of 0.5 followed by zeros.
+        bytes.extend_from_slice(&[2, 2, 0, 8]);
+        for value in &[128, 128, 128, 128] {
+            bytes.extend_from_slice(&[128 + 4, *value, 4, 0, 0, 0, 0]);
+        }
+        let (width, height, data) = parse_hdr(&bytes).unwrap();
+        assert_eq!((width, height), (8, 2));
+        assert_eq!((data[3].x, data[3].z), (1.0, 1.0));
+        assert_eq!((data[8].x, data[11].y), (0.5, 0.5));
+        assert_eq!((data[12].x, data[15].z), (0.0, 0.0));
+        assert!(parse_hdr(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn samples_match_pdf() {
+        // A single bright texel.
+        let mut data = vec![Color::new(0.01, 0.01, 0.01); 8 * 4];
+        data[8 + 5] = Color::new(100.0, 100.0, 100.0);
+        let env = EnvironmentMap::new(8, 4, data).with_rotation(Degrees::new(30.0));
+        for _ in 0..16 {
+            let (d, pdf) = env.sample().unwrap();
+            assert!((pdf - env.pdf(&d)).abs() < 1e-6 * pdf);
+            let (u, v) = env.direction_to_uv(&d);
+            let (u2, v2) = env.direction_to_uv(&env.uv_to_direction(u, v));
+            assert!((u - u2).abs() < 1e-9 && (v - v2).abs() < 1e-9);
+        }
+        // The center of the unrotated map is on the horizon towards +x.
+        let center = EnvironmentMap::new(8, 4, vec![Color::zeroed(); 32]).uv_to_direction(0.5, 0.5);
+        assert!((center.x - 1.0).abs() < 1e-9);
+        // The density integrates to one over the sphere.
+        let n = 256;
+        let mut integral = 0.0;
+        for j in 0..n {
+            for i in 0..2 * n {
+                let (u, v) = (
+                    (i as f64 + 0.5) / (2 * n) as f64,
+                    (j as f64 + 0.5) / n as f64,
+                );
+                let d = env.uv_to_direction(u, v);
+                let solid_angle = 2.0 * PI * PI * (PI * v).sin() / (2 * n * n) as f64;
+                integral += env.pdf(&d) * solid_angle;
+            }
+        }
+        assert!((integral - 1.0).abs() < 1e-2);
+    }
+}
@@ -4,12 +4,50 @@ use std::sync::Arc;
 pub struct HitRecord {
     pub p: Point3,
     pub normal: Point3,
+    /// Normal of the surface itself on the side of the incoming ray, not
+    /// smoothed by vertex normals, for deciding whether a ray crosses it.
+    pub geometric_normal: Point3,
     pub t: f64,
+    /// Surface texture coordinates.
+    pub u: f64,
+    pub v: f64,
+    /// Partial derivatives of the position with respect to `u` and `v`,
+    /// span the tangent plane for normal and bump mapping.
+    pub dpdu: Point3,
+    pub dpdv: Point3,
     pub front_face: bool,
+    /// Index of the primitive hit within its shape, e.g. a mesh's triangle.
+    pub primitive: usize,
+    /// Index of refraction on the other side of the surface from the
+    /// material's interior, set by the integrator for nested dielectrics.
+    pub exterior_ior: f64,
+    /// Set by `Material::scatter` when it picked the direction from a lobe
+    /// `pdf` doesn't cover, e.g. a mirror, which no other strategy samples.
+    pub specular: bool,
 
     pub material: Option<Arc<dyn Material + Send + Sync>>,
 }
 
+impl Default for HitRecord {
+    fn default() -> Self {
+        HitRecord {
+            p: Point3::default(),
+            normal: Point3::default(),
+            geometric_normal: Point3::default(),
+            t: Default::default(),
+            u: Default::default(),
+            v: Default::default(),
+            dpdu: Point3::default(),
+            dpdv: Point3::default(),
+            front_face: Default::default(),
+            primitive: 0,
+            exterior_ior: 1.0,
+            specular: false,
+            material: None,
+        }
+    }
+}
+
 impl Clone for HitRecord {
     fn clone(&self) -> Self {
         HitRecord {
@@ -20,15 +58,6 @@ impl Clone for HitRecord {
 }
 
 impl HitRecord {
-    pub fn default() -> Self {
-        HitRecord {
-            p: Point3::default(),
-            normal: Point3::default(),
-            t: Default::default(),
-            front_face: Default::default(),
-            material: None,
-        }
-    }
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: &Point3) {
         self.front_face = ray.direction().dot(outward_normal) < 0.0;
         self.normal = if self.front_face {
@@ -36,5 +65,6 @@ impl HitRecord {
         } else {
             -*outward_normal
         };
+        self.geometric_normal = self.normal;
     }
 }
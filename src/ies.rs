@@ -0,0 +1,210 @@
+//! IES LM-63 photometric profiles (1986 through 2002 revisions).
+//!
+//! Only type C photometry is supported, the common case for architectural
+//! luminaires: vertical angles go from the nadir (0°) to the zenith (180°)
+//! and horizontal angles turn around the vertical axis. Lamp tilt data is
+//! read but not applied.
+use super::*;
+use std::io;
+
+pub struct IesProfile {
+    /// Degrees, ascending.
+    vertical_angles: Vec<f64>,
+    /// Degrees, ascending, starting at 0.
+    horizontal_angles: Vec<f64>,
+    /// Candela for each horizontal angle, over the vertical angles.
+    candela: Vec<Vec<f64>>,
+    max_candela: f64,
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+impl IesProfile {
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        IesProfile::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(src: &str) -> io::Result<Self> {
+        let mut lines = src.lines();
+        // Keywords and the optional format line come before the tilt line.
+        let tilt = loop {
+            match lines.next() {
+                Some(line) if line.trim_start().starts_with("TILT=") => {
+                    break line.trim_start()["TILT=".len()..].trim().to_string()
+                }
+                Some(_) => continue,
+                None => return Err(invalid("IES file has no TILT line")),
+            }
+        };
+        let rest: Vec<&str> = lines.collect();
+        let words: Vec<&str> = rest
+            .iter()
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|word| !word.is_empty())
+            .collect();
+        let available = words.len();
+        let mut numbers = words.iter().map(|word| {
+            word.parse::<f64>()
+                .map_err(|_| invalid("IES file has a malformed number"))
+        });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or_else(|| Err(invalid("IES file is truncated")))
+        };
+        match tilt.as_str() {
+            "NONE" => {}
+            "INCLUDE" => {
+                let _geometry = next()?;
+                let pairs = next()? as usize;
+                if pairs > available / 2 {
+                    return Err(invalid("IES file is truncated"));
+                }
+                for _ in 0..2 * pairs {
+                    next()?;
+                }
+            }
+            _ => return Err(invalid("IES tilt files are not supported")),
+        }
+
+        let _lamps = next()?;
+        let _lumens_per_lamp = next()?;
+        let multiplier = next()?;
+        let vertical_count = next()? as usize;
+        let horizontal_count = next()? as usize;
+        let photometric_type = next()?;
+        let _units = next()?;
+        let (_width, _length, _height) = (next()?, next()?, next()?);
+        let ballast_factor = next()?;
+        let _future_use = next()?;
+        let _input_watts = next()?;
+        if photometric_type != 1.0 {
+            return Err(invalid("only type C IES photometry is supported"));
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err(invalid("IES file has no angles"));
+        }
+
+        let mut read = |count: usize| (0..count).map(|_| next()).collect::<io::Result<Vec<_>>>();
+        let vertical_angles = read(vertical_count)?;
+        let horizontal_angles = read(horizontal_count)?;
+        let scale = multiplier * ballast_factor;
+        let candela = (0..horizontal_count)
+            .map(|_| Ok(read(vertical_count)?.iter().map(|c| c * scale).collect()))
+            .collect::<io::Result<Vec<Vec<f64>>>>()?;
+
+        let ascending = |angles: &[f64]| angles.windows(2).all(|w| w[0] < w[1]);
+        if !ascending(&vertical_angles) || !ascending(&horizontal_angles) {
+            return Err(invalid("IES angles must be ascending"));
+        }
+        if horizontal_angles[0] != 0.0 {
+            return Err(invalid("IES horizontal angles must start at 0"));
+        }
+        let max_candela = candela
+            .iter()
+            .flatten()
+            .fold(0.0, |max: f64, c| max.max(*c));
+        Ok(IesProfile {
+            vertical_angles,
+            horizontal_angles,
+            candela,
+            max_candela,
+        })
+    }
+
+    pub fn max_candela(&self) -> f64 {
+        self.max_candela
+    }
+
+    /// Luminous intensity towards `vertical` degrees from the nadir and
+    /// `horizontal` degrees around it, interpolated bilinearly.
+    pub fn candela(&self, vertical: f64, horizontal: f64) -> f64 {
+        // Fold the horizontal angle into the range the file covers.
+        let mut h = horizontal.rem_euclid(360.0);
+        let last = *self.horizontal_angles.last().unwrap();
+        if last <= 180.0 && h > 180.0 {
+            // Bilateral symmetry about the 0-180 plane.
+            h = 360.0 - h;
+        }
+        if last <= 90.0 && h > 90.0 {
+            // Quadrant symmetry.
+            h = 180.0 - h;
+        }
+        let along = |angles: &[f64], x: f64| -> Option<(usize, f64)> {
+            if angles.len() == 1 {
+                return Some((0, 0.0));
+            }
+            if x < angles[0] || x > angles[angles.len() - 1] {
+                return None;
+            }
+            let i = angles
+                .partition_point(|a| *a <= x)
+                .clamp(1, angles.len() - 1)
+                - 1;
+            Some((i, (x - angles[i]) / (angles[i + 1] - angles[i])))
+        };
+        let (vi, vt) = match along(&self.vertical_angles, vertical) {
+            Some(v) => v,
+            None => return 0.0,
+        };
+        // Full 360° files may stop short of closing the circle.
+        let (hi, ht) = along(&self.horizontal_angles, h).unwrap_or((0, 0.0));
+        let at = |hi: usize| {
+            let row = &self.candela[hi];
+            let next = row[(vi + 1).min(row.len() - 1)];
+            (1.0 - vt) * row[vi] + vt * next
+        };
+        let next_h = (hi + 1).min(self.candela.len() - 1);
+        (1.0 - ht) * at(hi) + ht * at(next_h)
+    }
+
+    /// Intensity relative to the peak for the unit `direction` leaving the
+    /// luminaire, in `frame` whose `w` axis points at the nadir and `u` axis
+    /// at horizontal angle 0.
+    pub fn relative(&self, frame: &Onb, direction: &Point3) -> f64 {
+        if self.max_candela == 0.0 {
+            return 0.0;
+        }
+        let local = frame.to_local(direction);
+        let vertical = clamp(local.z, -1.0, 1.0).acos().to_degrees();
+        let horizontal = local.y.atan2(local.x).to_degrees();
+        self.candela(vertical, horizontal) / self.max_candela
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rotationally_symmetric_downlight() {
+        let profile = IesProfile::parse(include_str!("../tests/data/downlight.ies")).unwrap();
+        assert_eq!(profile.max_candela(), 1000.0);
+        assert_eq!(profile.candela(0.0, 0.0), 1000.0);
+        assert_eq!(profile.candela(45.0, 123.0), 700.0);
+        assert!((profile.candela(37.5, 300.0) - 800.0).abs() < 1e-9);
+        assert_eq!(profile.candela(120.0, 0.0), 0.0);
+
+        let frame = Onb::build_from_w(&(0, -1, 0).into());
+        assert_eq!(profile.relative(&frame, &(0, -1, 0).into()), 1.0);
+        let sideways = profile.relative(&frame, &Point3::new(1.0, -1.0, 0.0).unit());
+        assert!((sideways - 0.7).abs() < 1e-9);
+    }
+
+    #[test]
+    fn bilateral_wall_washer_with_tilt() {
+        let profile = IesProfile::parse(include_str!("../tests/data/wallwasher.ies")).unwrap();
+        // Candela multiplier of 2.
+        assert_eq!(profile.max_candela(), 400.0);
+        assert_eq!(profile.candela(45.0, 90.0), 240.0);
+        assert!((profile.candela(45.0, 45.0) - 270.0).abs() < 1e-9);
+        // Mirrored across the 0-180 plane.
+        assert_eq!(profile.candela(22.5, 270.0), profile.candela(22.5, 90.0));
+        assert_eq!(profile.candela(67.5, 180.0), 60.0);
+
+        assert!(IesProfile::parse("IESNA:LM-63-2002\nTILT=NONE\n1 1000 1").is_err());
+        let tilt = "IESNA:LM-63-2002\nTILT=INCLUDE\n1 1e300 0 0";
+        assert!(IesProfile::parse(tilt).is_err());
+    }
+}
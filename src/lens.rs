@@ -0,0 +1,411 @@
+//! Cameras tracing rays through a stack of spherical lens elements, after
+//! Kolb, Mitchell and Hanrahan, "A Realistic Camera Model for Computer
+//! Graphics Simulation" (1995). Vignetting, distortion and cat's-eye bokeh
+//! come out of the geometry.
+//!
+//! Lens space is in millimetres with the film at `z = 0` and the elements
+//! towards `+z`, one scene unit being a metre.
+use super::*;
+use crate::camera::{Camera, CameraSample};
+use std::io;
+
+/// Number of film radii the exit pupil is bounded for.
+const PUPIL_BINS: usize = 32;
+/// Samples along each side of the grid searched for the exit pupil.
+const PUPIL_GRID: usize = 48;
+
+/// One refracting surface, or the aperture stop.
+#[derive(Clone, Copy)]
+struct Interface {
+    /// Signed, positive with the center of curvature towards the film. Zero
+    /// for the flat aperture stop.
+    radius: f64,
+    /// Axial distance to the next surface towards the film.
+    thickness: f64,
+    /// Of the medium between this surface and the next, 1 for air.
+    ior: f64,
+    aperture_radius: f64,
+}
+
+/// A lens design read from text: one surface per line from the object side
+/// to the film, as radius, thickness, index of refraction and aperture
+/// diameter in millimetres. `#` starts a comment. The thickness of the last
+/// surface is its distance to the film, which focusing changes.
+pub struct LensPrescription {
+    interfaces: Vec<Interface>,
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, what)
+}
+
+impl LensPrescription {
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        LensPrescription::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(src: &str) -> io::Result<Self> {
+        let mut interfaces = Vec::new();
+        for line in src.lines() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let numbers = line
+                .split_whitespace()
+                .map(|word| word.parse::<f64>())
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|_| invalid("lens prescription has a malformed number"))?;
+            if numbers.len() != 4 {
+                return Err(invalid(
+                    "lens surfaces need a radius, thickness, ior and aperture",
+                ));
+            }
+            interfaces.push(Interface {
+                radius: numbers[0],
+                thickness: numbers[1],
+                ior: if numbers[2] == 0.0 { 1.0 } else { numbers[2] },
+                aperture_radius: numbers[3] / 2.0,
+            });
+        }
+        if interfaces.is_empty() {
+            return Err(invalid("lens prescription has no surfaces"));
+        }
+        Ok(LensPrescription { interfaces })
+    }
+}
+
+/// Axis-aligned rectangle on the plane of the rear element.
+#[derive(Clone, Copy)]
+struct Bounds2 {
+    min: (f64, f64),
+    max: (f64, f64),
+}
+
+impl Bounds2 {
+    fn area(&self) -> f64 {
+        (self.max.0 - self.min.0).max(0.0) * (self.max.1 - self.min.1).max(0.0)
+    }
+    fn lerp(&self, (u1, u2): (f64, f64)) -> (f64, f64) {
+        (
+            self.min.0 + u1 * (self.max.0 - self.min.0),
+            self.min.1 + u2 * (self.max.1 - self.min.1),
+        )
+    }
+}
+
+pub struct RealisticCamera {
+    interfaces: Vec<Interface>,
+    /// Film size in millimetres.
+    film: (f64, f64),
+    origin: Point3,
+    u: Point3,
+    v: Point3,
+    forward: Point3,
+    /// Where rays from film points at increasing radii can leave the rear
+    /// element, for points on the +x axis.
+    exit_pupils: Vec<Bounds2>,
+}
+
+impl RealisticCamera {
+    /// The film is a `sensor_diagonal` mm rectangle of `aspect_ratio`
+    /// centered at `lookfrom`, and the lens is moved so that objects
+    /// `focus_distance` away from the film are sharp.
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Point3,
+        prescription: LensPrescription,
+        sensor_diagonal: f64,
+        aspect_ratio: f64,
+        focus_distance: f64,
+    ) -> io::Result<Self> {
+        let forward = (lookat - lookfrom).unit();
+        let u = forward.cross(&vup).unit();
+        let v = u.cross(&forward);
+        let height = sensor_diagonal / (1.0 + aspect_ratio * aspect_ratio).sqrt();
+        let mut camera = RealisticCamera {
+            interfaces: prescription.interfaces,
+            film: (aspect_ratio * height, height),
+            origin: lookfrom,
+            u,
+            v,
+            forward,
+            exit_pupils: Vec::new(),
+        };
+        let thickness = camera.focus_thick_lens(1000.0 * focus_distance)?;
+        camera.interfaces.last_mut().unwrap().thickness = thickness;
+        camera.exit_pupils = (0..PUPIL_BINS)
+            .map(|bin| {
+                let half_diagonal = 0.5 * sensor_diagonal;
+                let r0 = bin as f64 / PUPIL_BINS as f64 * half_diagonal;
+                let r1 = (bin + 1) as f64 / PUPIL_BINS as f64 * half_diagonal;
+                camera.bound_exit_pupil(r0, r1)
+            })
+            .collect();
+        Ok(camera)
+    }
+
+    /// Position of the vertex of surface `i` on the axis.
+    fn vertex_z(&self, i: usize) -> f64 {
+        self.interfaces[i..].iter().map(|s| s.thickness).sum()
+    }
+    fn rear_z(&self) -> f64 {
+        self.vertex_z(self.interfaces.len() - 1)
+    }
+    fn rear_radius(&self) -> f64 {
+        self.interfaces.last().unwrap().aperture_radius
+    }
+
+    /// Index of refraction in front of surface `i`, on the object side.
+    fn ior_before(&self, i: usize) -> f64 {
+        if i == 0 {
+            1.0
+        } else {
+            self.interfaces[i - 1].ior
+        }
+    }
+
+    /// Refracts `ray` at surface `i` from `eta_i` into `eta_t`, `None` if
+    /// it misses the opening or is totally internally reflected.
+    fn interface(&self, i: usize, ray: &Ray, eta_i: f64, eta_t: f64) -> Option<Ray> {
+        let surface = &self.interfaces[i];
+        let z = self.vertex_z(i);
+        let d = ray.direction().unit();
+        let o = *ray.origin();
+        let (p, normal) = if surface.radius == 0.0 {
+            let t = (z - o.z) / d.z;
+            if t.is_nan() || t <= 0.0 {
+                return None;
+            }
+            (o + t * d, Point3::new(0.0, 0.0, 1.0))
+        } else {
+            let center = Point3::new(0.0, 0.0, z - surface.radius);
+            let oc = o - center;
+            let half_b = oc.dot(&d);
+            let discriminant =
+                half_b * half_b - (oc.len_squared() - surface.radius * surface.radius);
+            if discriminant < 0.0 {
+                return None;
+            }
+            let root = discriminant.sqrt();
+            // The cap around the vertex, not the far side of the sphere.
+            let t = [-half_b - root, -half_b + root]
+                .iter()
+                .copied()
+                .filter(|t| *t > 1e-9)
+                .find(|t| ((o + *t * d).z - center.z) * surface.radius > 0.0)?;
+            let p = o + t * d;
+            (p, (p - center) / surface.radius.abs())
+        };
+        if p.x * p.x + p.y * p.y > surface.aperture_radius * surface.aperture_radius {
+            return None;
+        }
+        if surface.radius == 0.0 {
+            return Some(Ray::new(p, d));
+        }
+        let n = if normal.dot(&d) > 0.0 {
+            -normal
+        } else {
+            normal
+        };
+        let eta = eta_i / eta_t;
+        let cos_i = -d.dot(&n);
+        if eta * eta * (1.0 - cos_i * cos_i) > 1.0 {
+            return None;
+        }
+        Some(Ray::new(p, d.refract(&n, eta)))
+    }
+
+    /// Follows a ray leaving the film out into the scene, `None` if an
+    /// element blocks it.
+    fn trace_from_film(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(*ray.origin(), *ray.direction());
+        for i in (0..self.interfaces.len()).rev() {
+            ray = self.interface(i, &ray, self.interfaces[i].ior, self.ior_before(i))?;
+        }
+        Some(ray)
+    }
+    fn trace_from_scene(&self, ray: &Ray) -> Option<Ray> {
+        let mut ray = Ray::new(*ray.origin(), *ray.direction());
+        for i in 0..self.interfaces.len() {
+            ray = self.interface(i, &ray, self.ior_before(i), self.interfaces[i].ior)?;
+        }
+        Some(ray)
+    }
+
+    /// Thickness of the last surface that focuses at `distance` mm from the
+    /// film, from the principal planes and focal length of the thick lens
+    /// the system approximates.
+    fn focus_thick_lens(&self, distance: f64) -> io::Result<f64> {
+        let height = 0.01 * self.rear_radius().min(self.interfaces[0].aperture_radius);
+        let unfocusable = || invalid("lens can't focus at that distance");
+        // Principal plane and focal point where a ray parallel to the axis
+        // at `height` comes out, in terms of distance towards the film.
+        let cardinal = |out: Ray| {
+            let (o, d) = (*out.origin(), *out.direction());
+            let focal = o.z + (-o.x / d.x) * d.z;
+            let principal = o.z + ((height - o.x) / d.x) * d.z;
+            (-principal, -focal)
+        };
+        let from_scene = Ray::new(
+            Point3::new(height, 0.0, self.vertex_z(0) + 1.0),
+            (0, 0, -1).into(),
+        );
+        let from_film = Ray::new(Point3::new(height, 0.0, 0.0), (0, 0, 1).into());
+        let (p0, f0) = cardinal(self.trace_from_scene(&from_scene).ok_or_else(unfocusable)?);
+        let (p1, _) = cardinal(self.trace_from_film(&from_film).ok_or_else(unfocusable)?);
+        let f = f0 - p0;
+        let z = -distance;
+        let c = (p1 - z - p0) * (p1 - z - 4.0 * f - p0);
+        if c.is_nan() || c <= 0.0 {
+            return Err(unfocusable());
+        }
+        let delta = 0.5 * (p1 - z + p0 - c.sqrt());
+        let thickness = self.interfaces.last().unwrap().thickness + delta;
+        if thickness > 0.0 {
+            Ok(thickness)
+        } else {
+            Err(unfocusable())
+        }
+    }
+
+    /// Bounds the points on the rear element through which rays from film
+    /// points between radii `r0` and `r1` on the +x axis make it out.
+    fn bound_exit_pupil(&self, r0: f64, r1: f64) -> Bounds2 {
+        let extent = 1.5 * self.rear_radius();
+        let step = 2.0 * extent / PUPIL_GRID as f64;
+        let rear_z = self.rear_z();
+        let mut bounds = Bounds2 {
+            min: (f64::INFINITY, f64::INFINITY),
+            max: (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        };
+        for k in 0..3 {
+            let x = r0 + (r1 - r0) * k as f64 / 2.0;
+            let film = Point3::new(x, 0.0, 0.0);
+            for i in 0..=PUPIL_GRID {
+                for j in 0..=PUPIL_GRID {
+                    let rear = (-extent + i as f64 * step, -extent + j as f64 * step);
+                    let target = Point3::new(rear.0, rear.1, rear_z);
+                    if self
+                        .trace_from_film(&Ray::new(film, target - film))
+                        .is_some()
+                    {
+                        bounds.min = (bounds.min.0.min(rear.0), bounds.min.1.min(rear.1));
+                        bounds.max = (bounds.max.0.max(rear.0), bounds.max.1.max(rear.1));
+                    }
+                }
+            }
+        }
+        if bounds.min.0 > bounds.max.0 {
+            // Nothing gets through, sample the whole element.
+            return Bounds2 {
+                min: (-extent, -extent),
+                max: (extent, extent),
+            };
+        }
+        // A grid step of margin for openings between the samples.
+        Bounds2 {
+            min: (bounds.min.0 - step, bounds.min.1 - step),
+            max: (bounds.max.0 + step, bounds.max.1 + step),
+        }
+    }
+
+    /// The lens ray for `sample` and its weight, `cos⁴θ` times the exit
+    /// pupil's area relative to the one at the center of the film.
+    fn lens_ray(&self, sample: &CameraSample) -> Option<(Ray, f64)> {
+        let (s, t) = sample.film;
+        // The lens turns the image upside down.
+        let film = Point3::new(-(s - 0.5) * self.film.0, -(t - 0.5) * self.film.1, 0.0);
+        let r = (film.x * film.x + film.y * film.y).sqrt();
+        let half_diagonal = 0.5 * (self.film.0 * self.film.0 + self.film.1 * self.film.1).sqrt();
+        let bin = ((r / half_diagonal * PUPIL_BINS as f64) as usize).min(PUPIL_BINS - 1);
+        let pupil = &self.exit_pupils[bin];
+        let (x, y) = pupil.lerp(sample.lens);
+        let (cos_phi, sin_phi) = if r > 0.0 {
+            (film.x / r, film.y / r)
+        } else {
+            (1.0, 0.0)
+        };
+        let rear = Point3::new(
+            cos_phi * x - sin_phi * y,
+            sin_phi * x + cos_phi * y,
+            self.rear_z(),
+        );
+        let direction = (rear - film).unit();
+        let out = self.trace_from_film(&Ray::new(film, direction))?;
+        let cos2 = direction.z * direction.z;
+        let weight = cos2 * cos2 * pupil.area() / self.exit_pupils[0].area();
+        Some((out, weight))
+    }
+}
+
+impl Camera for RealisticCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        self.generate_weighted_ray(sample).map(|(ray, _)| ray)
+    }
+    fn generate_weighted_ray(&self, sample: &CameraSample) -> Option<(Ray, f64)> {
+        let (ray, weight) = self.lens_ray(sample)?;
+        let to_world = |p: &Point3| p.x * self.u + p.y * self.v + p.z * self.forward;
+        let origin = self.origin + 0.001 * to_world(ray.origin());
+        let direction = to_world(ray.direction());
+        Some((Ray::new(origin, direction).with_time(sample.time), weight))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn double_gauss_focuses_and_vignettes() {
+        let lens = || LensPrescription::parse(include_str!("../tests/data/dgauss50.lens")).unwrap();
+        let camera = RealisticCamera::new(
+            Point3::zeroed(),
+            (0, 0, -1).into(),
+            (0, 1, 0).into(),
+            lens(),
+            43.27,
+            1.5,
+            2.0,
+        )
+        .unwrap();
+        // Rays from the center of the film meet again on the axis 2 m out.
+        for x in [-2.0, -1.0, 1.0, 2.0] {
+            let target = Point3::new(x, 0.0, camera.rear_z());
+            let out = camera
+                .trace_from_film(&Ray::new(Point3::zeroed(), target))
+                .unwrap();
+            let (o, d) = (*out.origin(), out.direction().unit());
+            let z = o.z + (-o.x / d.x) * d.z;
+            assert!((z - 2000.0).abs() < 0.02 * 2000.0);
+        }
+        // The back focus of a 50 mm double Gauss, a little shorter than its focal length.
+        assert!(camera.rear_z() > 30.0 && camera.rear_z() < 45.0);
+
+        let sample = |film| CameraSample {
+            film,
+            lens: (0.5, 0.5),
+            ..Default::default()
+        };
+        let (center, weight) = camera.generate_weighted_ray(&sample((0.5, 0.5))).unwrap();
+        assert!((center.direction().unit().z + 1.0).abs() < 1e-9);
+        assert!((weight - 1.0).abs() < 1e-9);
+        // Upright image: the right of the film looks right.
+        let (right, corner_weight) = camera.generate_weighted_ray(&sample((0.95, 0.5))).unwrap();
+        assert!(right.direction().x > 0.0);
+        assert!(corner_weight < weight);
+
+        assert!(LensPrescription::parse("50 3 1.5").is_err());
+        assert!(LensPrescription::parse("# nothing\n").is_err());
+        assert!(RealisticCamera::new(
+            Point3::zeroed(),
+            (0, 0, -1).into(),
+            (0, 1, 0).into(),
+            lens(),
+            43.27,
+            1.5,
+            0.01,
+        )
+        .is_err());
+    }
+}
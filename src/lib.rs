@@ -0,0 +1,65 @@
+pub mod alpha;
+pub mod bump;
+pub mod camera;
+pub mod coated;
+pub mod distribution;
+pub mod environment;
+pub mod hitrecord;
+pub mod ies;
+pub mod lens;
+pub mod light;
+pub mod lightsampler;
+pub mod material;
+pub mod medium;
+pub mod merl;
+pub mod mesh;
+pub mod microfacet;
+pub mod mix;
+pub mod mtl;
+pub mod onb;
+pub mod output;
+pub mod panorama;
+pub mod principled;
+pub mod quad;
+pub mod ray;
+pub mod shader;
+pub mod sky;
+pub mod sphere;
+pub mod stereo;
+pub mod subsurface;
+pub mod texture;
+pub mod thinfilm;
+pub mod traits;
+pub mod vec3;
+
+pub type Color = Vec3<vec3::Color>;
+pub type Point3 = Vec3<vec3::Point3>;
+
+use hitrecord::*;
+use material::*;
+use medium::*;
+use microfacet::*;
+use onb::*;
+use principled::*;
+use rand::random;
+use ray::Ray;
+use texture::*;
+use thinfilm::*;
+use traits::*;
+use vec3::Vec3;
+
+pub fn clamp<T>(val: T, min: T, max: T) -> T
+where
+    T: Copy + std::cmp::PartialOrd,
+{
+    if val < min {
+        min
+    } else if val > max {
+        max
+    } else {
+        val
+    }
+}
+pub fn random_range(min: f64, max: f64) -> f64 {
+    min + (max - min) * rand::random::<f64>()
+}
@@ -0,0 +1,647 @@
+//! Lights sampled by next-event estimation. Punctual lights have no surface
+//! and can't be hit by rays; sphere and mesh lights mirror emissive surfaces
+//! in the world, so their emission is also found by scattered rays and combined
+//! with multiple importance sampling.
+use super::*;
+use crate::camera::Degrees;
+use crate::distribution::AliasTable;
+use crate::environment::power_heuristic;
+use crate::ies::IesProfile;
+use crate::lightsampler::{LightBounds, LightSampler};
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Incident light at a shading point.
+pub struct LightSample {
+    /// Unit direction from the shading point towards the light.
+    pub wi: Point3,
+    /// Distance to the light, infinite for directional lights.
+    pub distance: f64,
+    /// Radiance arriving along `wi`, already including falloff.
+    pub radiance: Color,
+    /// Solid angle density of `wi`, zero for lights that can't be hit by
+    /// rays. Those give the incident term in `radiance` directly.
+    pub pdf: f64,
+}
+
+pub trait Light {
+    /// `None` if the light doesn't reach `p`.
+    fn sample(&self, p: &Point3) -> Option<LightSample>;
+    /// Density with which `sample` at `p` picks `wi`, given the ray along it
+    /// first hits `hit`.
+    fn pdf(&self, _p: &Point3, _wi: &Point3, _hit: &HitRecord) -> f64 {
+        0.0
+    }
+    /// Extent and power for the light sampler, `None` for lights infinitely
+    /// far away.
+    fn bounds(&self) -> Option<LightBounds> {
+        None
+    }
+}
+
+/// Measured distribution modulating a light's intensity.
+struct Photometry {
+    profile: Arc<IesProfile>,
+    /// `w` points at the profile's nadir, `u` along its 0° horizontal angle.
+    frame: Onb,
+}
+
+impl Photometry {
+    fn new(profile: Arc<IesProfile>, nadir: &Point3, zero: &Point3) -> Self {
+        Photometry {
+            profile,
+            frame: Onb::from_normal_tangent(nadir, zero),
+        }
+    }
+    /// Scale for light leaving towards `-wi`.
+    fn scale(photometry: &Option<Photometry>, wi: &Point3) -> f64 {
+        match photometry {
+            Some(p) => p.profile.relative(&p.frame, &-*wi),
+            None => 1.0,
+        }
+    }
+}
+
+/// Point light with intensity in W/sr per channel, isotropic unless it has
+/// a photometric profile.
+pub struct PointLight {
+    position: Point3,
+    intensity: Color,
+    radius: f64,
+    photometry: Option<Photometry>,
+}
+
+impl PointLight {
+    pub fn new(position: Point3, intensity: Color) -> Self {
+        PointLight {
+            position,
+            intensity,
+            radius: f64::INFINITY,
+            photometry: None,
+        }
+    }
+    /// Smoothly fades the inverse square falloff to zero at `radius`.
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+    /// Modulates the intensity by an IES candela distribution whose nadir
+    /// points along `nadir` and whose 0° horizontal angle faces `zero`; the
+    /// intensity becomes that of the profile's peak.
+    pub fn with_profile(mut self, profile: Arc<IesProfile>, nadir: Point3, zero: Point3) -> Self {
+        self.photometry = Some(Photometry::new(profile, &nadir, &zero));
+        self
+    }
+}
+
+/// Inverse square falloff windowed by `(1 - (d / radius)^4)^2`, which keeps
+/// the light physically plausible close by and bounded in range.
+fn falloff(distance: f64, radius: f64) -> f64 {
+    let ratio = distance / radius;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    window * window / (distance * distance)
+}
+
+fn sample_position(
+    p: &Point3,
+    position: &Point3,
+    intensity: Color,
+    radius: f64,
+) -> Option<LightSample> {
+    let to_light = *position - *p;
+    let distance = to_light.len();
+    let attenuation = falloff(distance, radius);
+    if distance == 0.0 || attenuation == 0.0 {
+        return None;
+    }
+    Some(LightSample {
+        wi: to_light / distance,
+        distance,
+        radiance: attenuation * intensity,
+        pdf: 0.0,
+    })
+}
+
+impl Light for PointLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let mut sample = sample_position(p, &self.position, self.intensity, self.radius)?;
+        sample.radiance = Photometry::scale(&self.photometry, &sample.wi) * sample.radiance;
+        Some(sample)
+    }
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = 4.0 * PI * self.intensity.luminance();
+        Some(LightBounds::omnidirectional(
+            self.position,
+            self.position,
+            phi,
+        ))
+    }
+}
+
+/// Point light restricted to a cone, fading out between the inner and outer
+/// cone angles measured from its axis. The inner angle must not exceed the
+/// outer one.
+pub struct SpotLight {
+    position: Point3,
+    direction: Point3,
+    intensity: Color,
+    cos_inner: f64,
+    cos_outer: f64,
+    radius: f64,
+    photometry: Option<Photometry>,
+}
+
+impl SpotLight {
+    pub fn new(
+        position: Point3,
+        target: Point3,
+        intensity: Color,
+        inner: Degrees,
+        outer: Degrees,
+    ) -> Self {
+        let inner: f64 = inner.to_radians().into();
+        let outer: f64 = outer.to_radians().into();
+        SpotLight {
+            position,
+            direction: (target - position).unit(),
+            intensity,
+            cos_inner: inner.cos(),
+            cos_outer: outer.cos(),
+            radius: f64::INFINITY,
+            photometry: None,
+        }
+    }
+    /// Smoothly fades the inverse square falloff to zero at `radius`.
+    pub fn with_radius(mut self, radius: f64) -> Self {
+        self.radius = radius;
+        self
+    }
+    /// Modulates the intensity by an IES candela distribution aimed with its
+    /// nadir along the spot axis and its 0° horizontal angle towards `zero`,
+    /// on top of the cone falloff.
+    pub fn with_profile(mut self, profile: Arc<IesProfile>, zero: Point3) -> Self {
+        self.photometry = Some(Photometry::new(profile, &self.direction, &zero));
+        self
+    }
+
+    /// Smoothstep from the outer to the inner cone.
+    fn cone(&self, cos: f64) -> f64 {
+        if self.cos_inner <= self.cos_outer {
+            return if cos >= self.cos_outer { 1.0 } else { 0.0 };
+        }
+        let t = clamp(
+            (cos - self.cos_outer) / (self.cos_inner - self.cos_outer),
+            0.0,
+            1.0,
+        );
+        t * t * (3.0 - 2.0 * t)
+    }
+}
+
+impl Light for SpotLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let mut sample = sample_position(p, &self.position, self.intensity, self.radius)?;
+        let cone = self.cone(-sample.wi.dot(&self.direction))
+            * Photometry::scale(&self.photometry, &sample.wi);
+        if cone == 0.0 {
+            return None;
+        }
+        sample.radiance = cone * sample.radiance;
+        Some(sample)
+    }
+    /// The inner cone at full intensity, and the falloff as a linear ramp.
+    fn bounds(&self) -> Option<LightBounds> {
+        let phi = self.intensity.luminance()
+            * 2.0
+            * PI
+            * ((1.0 - self.cos_inner) + (self.cos_inner - self.cos_outer) / 2.0);
+        let theta_inner = clamp(self.cos_inner, -1.0, 1.0).acos();
+        let theta_outer = clamp(self.cos_outer, -1.0, 1.0).acos();
+        Some(LightBounds {
+            min: self.position,
+            max: self.position,
+            phi,
+            w: self.direction,
+            cos_theta_o: self.cos_inner,
+            cos_theta_e: (theta_outer - theta_inner).max(0.0).cos(),
+            two_sided: false,
+        })
+    }
+}
+
+/// Infinitely distant light such as the sun, with irradiance in W/m² per
+/// channel on a surface facing it.
+pub struct DirectionalLight {
+    /// Unit direction the light travels in.
+    direction: Point3,
+    irradiance: Color,
+}
+
+impl DirectionalLight {
+    pub fn new(direction: Point3, irradiance: Color) -> Self {
+        DirectionalLight {
+            direction: direction.unit(),
+            irradiance,
+        }
+    }
+}
+
+impl Light for DirectionalLight {
+    fn sample(&self, _: &Point3) -> Option<LightSample> {
+        Some(LightSample {
+            wi: -self.direction,
+            distance: f64::INFINITY,
+            radiance: self.irradiance,
+            pdf: 0.0,
+        })
+    }
+}
+
+/// Emission of a sphere with a `DiffuseLight` material, sampled uniformly
+/// over the cone it subtends. The sphere itself must be in the world too.
+pub struct SphereLight {
+    center: Point3,
+    radius: f64,
+    emit: Color,
+}
+
+impl SphereLight {
+    pub fn new(center: Point3, radius: f64, emit: Color) -> Self {
+        SphereLight {
+            center,
+            radius,
+            emit,
+        }
+    }
+
+    /// `1 - cos` of the subtended cone's half angle, `None` inside the sphere.
+    fn cone(&self, p: &Point3) -> Option<f64> {
+        let distance_squared = (self.center - *p).len_squared();
+        let sin2 = self.radius * self.radius / distance_squared;
+        if sin2 >= 1.0 {
+            return None;
+        }
+        // Stable for small cones.
+        Some(sin2 / (1.0 + (1.0 - sin2).sqrt()))
+    }
+    /// Distance from `p` along the unit `wi` to the near side of the sphere.
+    fn intersect(&self, p: &Point3, wi: &Point3) -> Option<f64> {
+        let oc = *p - self.center;
+        let half_b = oc.dot(wi);
+        let discriminant = half_b * half_b - (oc.len_squared() - self.radius * self.radius);
+        if discriminant < 0.0 {
+            return None;
+        }
+        let t = -half_b - discriminant.sqrt();
+        if t > 0.0 {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+impl Light for SphereLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let one_minus_cos_max = self.cone(p)?;
+        let one_minus_cos = random::<f64>() * one_minus_cos_max;
+        let cos_theta = 1.0 - one_minus_cos;
+        let sin_theta = (one_minus_cos * (2.0 - one_minus_cos)).sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+        let local = Point3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        let wi = Onb::build_from_w(&(self.center - *p)).local(&local).unit();
+        // Grazing directions can miss by rounding, the tangent distance is close.
+        let distance = self
+            .intersect(p, &wi)
+            .unwrap_or_else(|| (self.center - *p).dot(&wi));
+        Some(LightSample {
+            wi,
+            distance,
+            radiance: self.emit,
+            pdf: 1.0 / (2.0 * PI * one_minus_cos_max),
+        })
+    }
+    fn pdf(&self, p: &Point3, wi: &Point3, hit: &HitRecord) -> f64 {
+        let one_minus_cos_max = match self.cone(p) {
+            Some(cone) => cone,
+            None => return 0.0,
+        };
+        let distance = (hit.p - *p).len();
+        match self.intersect(p, wi) {
+            Some(t) if (t - distance).abs() <= 1e-4 * distance.max(1.0) => {
+                1.0 / (2.0 * PI * one_minus_cos_max)
+            }
+            _ => 0.0,
+        }
+    }
+    fn bounds(&self) -> Option<LightBounds> {
+        let r: Point3 = (self.radius, self.radius, self.radius).into();
+        let area = 4.0 * PI * self.radius * self.radius;
+        Some(LightBounds::omnidirectional(
+            self.center - r,
+            self.center + r,
+            PI * area * self.emit.luminance(),
+        ))
+    }
+}
+
+/// Emission from the front faces of a triangle mesh with a `DiffuseLight`
+/// material, sampled uniformly by area. The triangles must be in the world
+/// too, in the same order, so hits identify theirs by `HitRecord::primitive`.
+pub struct MeshLight {
+    triangles: Vec<[Point3; 3]>,
+    /// Over the triangles, by area.
+    areas: AliasTable,
+    area: f64,
+    emit: Color,
+}
+
+impl MeshLight {
+    /// `None` without any area to emit from.
+    pub fn new(triangles: Vec<[Point3; 3]>, emit: Color) -> Option<Self> {
+        let areas: Vec<f64> = triangles
+            .iter()
+            .map(|t| 0.5 * geometric_normal(t).len())
+            .collect();
+        let area: f64 = areas.iter().sum();
+        if area == 0.0 {
+            return None;
+        }
+        Some(MeshLight {
+            areas: AliasTable::new(&areas),
+            triangles,
+            area,
+            emit,
+        })
+    }
+
+    /// Solid angle density of reaching `q` on a face with unit normal `n` from `p`.
+    fn solid_angle_pdf(&self, p: &Point3, q: &Point3, n: &Point3) -> f64 {
+        let to_light = *q - *p;
+        let distance_squared = to_light.len_squared();
+        // Only the front face emits.
+        let cos = -n.dot(&to_light) / distance_squared.sqrt();
+        if cos <= 0.0 {
+            return 0.0;
+        }
+        distance_squared / (cos * self.area)
+    }
+}
+
+/// Facing the winding order, with the length of twice the area.
+fn geometric_normal([p0, p1, p2]: &[Point3; 3]) -> Point3 {
+    (*p1 - *p0).cross(&(*p2 - *p0))
+}
+
+impl Light for MeshLight {
+    fn sample(&self, p: &Point3) -> Option<LightSample> {
+        let (index, _) = self.areas.sample(random());
+        let triangle = &self.triangles[index];
+        let [p0, p1, p2] = *triangle;
+        // Uniform barycentrics.
+        let root = random::<f64>().sqrt();
+        let (b1, b2) = (1.0 - root, random::<f64>() * root);
+        let q = p0 + b1 * (p1 - p0) + b2 * (p2 - p0);
+        let pdf = self.solid_angle_pdf(p, &q, &geometric_normal(triangle).unit());
+        if pdf == 0.0 || !pdf.is_finite() {
+            return None;
+        }
+        let to_light = q - *p;
+        let distance = to_light.len();
+        Some(LightSample {
+            wi: to_light / distance,
+            distance,
+            radiance: self.emit,
+            pdf,
+        })
+    }
+    fn pdf(&self, p: &Point3, _wi: &Point3, hit: &HitRecord) -> f64 {
+        let triangle = match self.triangles.get(hit.primitive) {
+            Some(triangle) => triangle,
+            None => return 0.0,
+        };
+        // Hits on other shapes with the same primitive index land off it.
+        let q = hit.p;
+        let n = geometric_normal(triangle);
+        let length = n.len();
+        let tolerance = 1e-4 * (q - *p).len().max(1.0) * length;
+        let on_triangle = length > 0.0
+            && (q - triangle[0]).dot(&n).abs() <= tolerance
+            && (0..3).all(|k| {
+                let edge = triangle[(k + 1) % 3] - triangle[k];
+                edge.cross(&(q - triangle[k])).dot(&n) >= -tolerance
+            });
+        if !on_triangle {
+            return 0.0;
+        }
+        self.solid_angle_pdf(p, &q, &(n / length))
+    }
+    /// The normal cone around the area weighted mean normal.
+    fn bounds(&self) -> Option<LightBounds> {
+        let mut min = self.triangles[0][0];
+        let mut max = min;
+        let mut mean = Point3::zeroed();
+        for triangle in &self.triangles {
+            for v in triangle {
+                min = Point3::new(min.x.min(v.x), min.y.min(v.y), min.z.min(v.z));
+                max = Point3::new(max.x.max(v.x), max.y.max(v.y), max.z.max(v.z));
+            }
+            mean += geometric_normal(triangle);
+        }
+        let phi = PI * self.area * self.emit.luminance();
+        if mean.len_squared() < 1e-24 {
+            return Some(LightBounds::omnidirectional(min, max, phi));
+        }
+        let w = mean.unit();
+        let cos_theta_o = self
+            .triangles
+            .iter()
+            .map(geometric_normal)
+            .filter(|n| n.len_squared() > 0.0)
+            .fold(1.0, |cos: f64, n| cos.min(n.unit().dot(&w)));
+        Some(LightBounds {
+            min,
+            max,
+            phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        })
+    }
+}
+
+/// Light reflected towards `r_in` by `material` at `rec` from one light
+/// picked by `sampler`, with a shadow ray against `world`. `medium` is the
+/// one surrounding the shading point on the side of the incoming ray, if any.
+pub fn direct_lighting(
+    lights: &[Box<dyn Light + Send + Sync>],
+    sampler: &dyn LightSampler,
+    world: &impl Hittable,
+    medium: Option<&Medium>,
+    r_in: &Ray,
+    rec: &HitRecord,
+    material: &dyn Material,
+) -> Color {
+    let (index, pmf) = match sampler.sample(&rec.p, &rec.normal, random()) {
+        Some(picked) => picked,
+        None => return Color::zeroed(),
+    };
+    let sample = match lights[index].sample(&rec.p) {
+        Some(sample) => sample,
+        None => return Color::zeroed(),
+    };
+    let f = material.eval(r_in, rec, &sample.wi);
+    if f.x == 0.0 && f.y == 0.0 && f.z == 0.0 {
+        return Color::zeroed();
+    }
+    // Stop short of lights with a surface so they don't shadow themselves.
+    let t_max = if sample.pdf > 0.0 {
+        sample.distance * (1.0 - 1e-4)
+    } else {
+        sample.distance
+    };
+    let shadow = Ray::new(rec.p, sample.wi);
+    let mut shadow_rec = HitRecord::default();
+    if world.hit(&shadow, 0.001, t_max, &mut shadow_rec) {
+        return Color::zeroed();
+    }
+    // Media are enclosed, so unoccluded shadow rays have a finite length in them.
+    let transmittance = match medium {
+        Some(medium) if sample.distance.is_finite() => medium.transmittance(sample.distance),
+        _ => (1, 1, 1).into(),
+    };
+    if sample.pdf == 0.0 {
+        return (1.0 / pmf) * (f * transmittance * sample.radiance);
+    }
+    // Shared with the BSDF sample that may hit the same light.
+    let light_pdf = pmf * sample.pdf;
+    let mis = power_heuristic(light_pdf, material.pdf(r_in, rec, &sample.wi));
+    (mis / light_pdf) * (f * transmittance * sample.radiance)
+}
+
+/// Density with which `direct_lighting` at `origin` with normal `normal`
+/// would have sampled the unit direction `wi` towards the emitter `hit`.
+pub fn emitter_pdf(
+    lights: &[Box<dyn Light + Send + Sync>],
+    sampler: &dyn LightSampler,
+    origin: &Point3,
+    normal: &Point3,
+    wi: &Point3,
+    hit: &HitRecord,
+) -> f64 {
+    sampler
+        .lights_at(&hit.p)
+        .into_iter()
+        .map(|i| sampler.pmf(origin, normal, i) * lights[i].pdf(origin, wi, hit))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Where a ray from `p` along `wi` ends after `distance`.
+    fn hit(p: &Point3, wi: &Point3, distance: f64, primitive: usize) -> HitRecord {
+        HitRecord {
+            p: *p + distance * *wi,
+            primitive,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn falloff_and_cone() {
+        let light = PointLight::new((0, 2, 0).into(), (4, 4, 4).into());
+        let sample = light.sample(&Point3::zeroed()).unwrap();
+        assert!((sample.radiance.x - 1.0).abs() < 1e-12);
+        assert!((sample.wi.y - 1.0).abs() < 1e-12);
+        let ranged = PointLight::new((0, 2, 0).into(), (4, 4, 4).into()).with_radius(2.0);
+        assert!(ranged.sample(&Point3::zeroed()).is_none());
+
+        let spot = SpotLight::new(
+            (0, 1, 0).into(),
+            Point3::zeroed(),
+            (1, 1, 1).into(),
+            Degrees::new(10.0),
+            Degrees::new(20.0),
+        );
+        assert!((spot.sample(&Point3::zeroed()).unwrap().radiance.x - 1.0).abs() < 1e-12);
+        // 15° off the axis lies between the cones.
+        let between = spot.sample(&(15f64.to_radians().tan(), 0.0, 0.0).into());
+        let cone = between.unwrap().radiance.x / 15f64.to_radians().cos().powi(2);
+        assert!(cone > 0.0 && cone < 1.0);
+        assert!(spot.sample(&(1, 0, 0).into()).is_none());
+
+        let profile =
+            Arc::new(IesProfile::parse(include_str!("../tests/data/downlight.ies")).unwrap());
+        let downlight = PointLight::new((0, 1, 0).into(), (1, 1, 1).into()).with_profile(
+            profile,
+            (0, -1, 0).into(),
+            (1, 0, 0).into(),
+        );
+        let sample = downlight.sample(&(1, 0, 0).into()).unwrap();
+        assert!((sample.radiance.x - 0.5 * 0.7).abs() < 1e-9);
+
+        // Aimed so the wall washer's 90° plane faces +z.
+        let profile =
+            Arc::new(IesProfile::parse(include_str!("../tests/data/wallwasher.ies")).unwrap());
+        let washer = PointLight::new((0, 1, 0).into(), (1, 1, 1).into()).with_profile(
+            profile,
+            (0, -1, 0).into(),
+            (1, 0, 0).into(),
+        );
+        let sample = washer.sample(&(0, 0, 1).into()).unwrap();
+        assert!((sample.radiance.x - 0.5 * 240.0 / 400.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn sphere_light_cone() {
+        let light = SphereLight::new((0, 0, 4).into(), 1.0, (2, 2, 2).into());
+        let p = Point3::zeroed();
+        // sin²θ = 1/16, so the cone covers 2π(1 - √15/4).
+        let expected = 1.0 / (2.0 * PI * (1.0 - 15f64.sqrt() / 4.0));
+        for _ in 0..100 {
+            let sample = light.sample(&p).unwrap();
+            assert!((sample.pdf - expected).abs() < 1e-9 * expected);
+            let end = hit(&p, &sample.wi, sample.distance, 0);
+            assert!((light.pdf(&p, &sample.wi, &end) - sample.pdf).abs() < 1e-9);
+            assert!(sample.distance >= 3.0 - 1e-9 && sample.distance <= 15f64.sqrt() + 1e-9);
+        }
+        // Occluded before the sphere, or missing it altogether.
+        let wi = (0, 0, 1).into();
+        assert_eq!(light.pdf(&p, &wi, &hit(&p, &wi, 2.0, 0)), 0.0);
+        let wi = (0, 1, 0).into();
+        assert_eq!(light.pdf(&p, &wi, &hit(&p, &wi, 100.0, 0)), 0.0);
+        assert!(light.sample(&(0, 0, 4).into()).is_none());
+    }
+
+    #[test]
+    fn mesh_light_area_sampling() {
+        // A 2x1 rectangle at y = 2 facing down, split unevenly.
+        let triangles = vec![
+            [(0, 2, 0).into(), (2, 2, 0).into(), (2, 2, 1).into()],
+            [(0, 2, 0).into(), (2, 2, 1).into(), (0, 2, 1).into()],
+        ];
+        let light = MeshLight::new(triangles, (1, 1, 1).into()).unwrap();
+        let p = Point3::new(1.0, 0.0, 0.5);
+        let up = (0, 1, 0).into();
+        let straight = light.pdf(&p, &up, &hit(&p, &up, 2.0, 0));
+        assert!((straight - 2.0).abs() < 1e-9);
+        for _ in 0..100 {
+            let sample = light.sample(&p).unwrap();
+            // Only the triangle that was hit counts.
+            let pdf: f64 = (0..3)
+                .map(|i| light.pdf(&p, &sample.wi, &hit(&p, &sample.wi, sample.distance, i)))
+                .sum();
+            assert!((pdf - sample.pdf).abs() < 1e-9 * pdf);
+        }
+        // The back face doesn't emit.
+        assert!(light.sample(&(1, 3, 0).into()).is_none());
+        let bounds = light.bounds().unwrap();
+        assert!((bounds.w.y + 1.0).abs() < 1e-9 && (bounds.cos_theta_o - 1.0).abs() < 1e-9);
+
+        let quad = vec![[(0, 0, 0).into(), (1, 0, 0).into(), (1, 0, 0).into()]];
+        assert!(MeshLight::new(quad, (1, 1, 1).into()).is_none());
+    }
+}
@@ -0,0 +1,476 @@
+//! Choosing one light per shading point in scenes with many of them.
+//!
+//! Lights infinitely far away have no bounds and are picked uniformly,
+//! sharing the probability with the bounded ones as if those were a single
+//! light.
+use super::*;
+use crate::distribution::AliasTable;
+use crate::light::Light;
+use std::f64::consts::PI;
+
+/// Spatial and directional extent of the light a light emits, after
+/// Conty Estevez and Kulla, "Importance Sampling of Many Lights with
+/// Adaptive Tree Splitting" (2018).
+#[derive(Clone, Copy)]
+pub struct LightBounds {
+    pub min: Point3,
+    pub max: Point3,
+    /// Emitted power, luminance weighted.
+    pub phi: f64,
+    /// Axis of the cone bounding the emitter normals.
+    pub w: Point3,
+    /// Cosine of the cone's half angle.
+    pub cos_theta_o: f64,
+    /// Cosine of the angle beyond the normals that light still leaves at.
+    pub cos_theta_e: f64,
+    pub two_sided: bool,
+}
+
+impl LightBounds {
+    /// Bounds of a light emitting in every direction from a box.
+    pub fn omnidirectional(min: Point3, max: Point3, phi: f64) -> Self {
+        LightBounds {
+            min,
+            max,
+            phi,
+            w: (0, 0, 1).into(),
+            cos_theta_o: -1.0,
+            cos_theta_e: 0.0,
+            two_sided: false,
+        }
+    }
+
+    fn centroid(&self) -> Point3 {
+        0.5 * (self.min + self.max)
+    }
+    fn contains(&self, p: &Point3) -> bool {
+        let eps = 1e-6 * (1.0 + (self.max - self.min).len());
+        p.x >= self.min.x - eps
+            && p.y >= self.min.y - eps
+            && p.z >= self.min.z - eps
+            && p.x <= self.max.x + eps
+            && p.y <= self.max.y + eps
+            && p.z <= self.max.z + eps
+    }
+
+    pub fn union(&self, other: &LightBounds) -> LightBounds {
+        if self.phi == 0.0 {
+            return *other;
+        }
+        if other.phi == 0.0 {
+            return *self;
+        }
+        let (w, cos_theta_o) =
+            union_cones((self.w, self.cos_theta_o), (other.w, other.cos_theta_o));
+        LightBounds {
+            min: Point3::new(
+                self.min.x.min(other.min.x),
+                self.min.y.min(other.min.y),
+                self.min.z.min(other.min.z),
+            ),
+            max: Point3::new(
+                self.max.x.max(other.max.x),
+                self.max.y.max(other.max.y),
+                self.max.z.max(other.max.z),
+            ),
+            phi: self.phi + other.phi,
+            w,
+            cos_theta_o,
+            cos_theta_e: self.cos_theta_e.min(other.cos_theta_e),
+            two_sided: self.two_sided || other.two_sided,
+        }
+    }
+
+    /// Conservative estimate of the light reaching `p` on a surface with
+    /// normal `n`, or a zero normal inside media.
+    pub fn importance(&self, p: &Point3, n: &Point3) -> f64 {
+        let center = self.centroid();
+        let radius = 0.5 * (self.max - self.min).len();
+        let to_p = *p - center;
+        let distance_squared = to_p.len_squared();
+        // Keep the estimate finite close to and inside the bounds.
+        let d2 = distance_squared.max(radius);
+        let wi = if distance_squared > 0.0 {
+            to_p / distance_squared.sqrt()
+        } else {
+            self.w
+        };
+        let mut cos_w = self.w.dot(&wi);
+        if self.two_sided {
+            cos_w = cos_w.abs();
+        }
+        // Angle subtended by the bounds' enclosing sphere.
+        let theta_b = if distance_squared < radius * radius {
+            PI
+        } else {
+            (radius * radius / distance_squared).sqrt().asin()
+        };
+        let theta_w = clamp(cos_w, -1.0, 1.0).acos();
+        let theta_o = clamp(self.cos_theta_o, -1.0, 1.0).acos();
+        let cos_p = (theta_w - theta_o - theta_b).max(0.0).cos();
+        if cos_p <= self.cos_theta_e {
+            return 0.0;
+        }
+        let mut importance = self.phi * cos_p / d2;
+        if n.len_squared() > 0.0 {
+            let theta_i = clamp(wi.dot(n).abs(), 0.0, 1.0).acos();
+            importance *= (theta_i - theta_b).max(0.0).cos();
+        }
+        importance.max(0.0)
+    }
+}
+
+/// Smallest cone containing both, as `(axis, cos half angle)`.
+fn union_cones(a: (Point3, f64), b: (Point3, f64)) -> (Point3, f64) {
+    let theta_a = clamp(a.1, -1.0, 1.0).acos();
+    let theta_b = clamp(b.1, -1.0, 1.0).acos();
+    let theta_d = clamp(a.0.dot(&b.0), -1.0, 1.0).acos();
+    if (theta_d + theta_b).min(PI) <= theta_a {
+        return a;
+    }
+    if (theta_d + theta_a).min(PI) <= theta_b {
+        return b;
+    }
+    let theta_o = 0.5 * (theta_a + theta_d + theta_b);
+    let everywhere = ((0, 0, 1).into(), -1.0);
+    if theta_o >= PI {
+        return everywhere;
+    }
+    let axis = a.0.cross(&b.0);
+    if axis.len_squared() < 1e-24 {
+        return everywhere;
+    }
+    (a.0.rotate(&axis.unit(), theta_o - theta_a), theta_o.cos())
+}
+
+pub trait LightSampler {
+    /// Picks a light for the shading point `p` with normal `n`, with its probability.
+    fn sample(&self, p: &Point3, n: &Point3, u: f64) -> Option<(usize, f64)>;
+    /// Probability that `sample` picks `light` at `p`.
+    fn pmf(&self, p: &Point3, n: &Point3, light: usize) -> f64;
+    /// Lights whose bounds contain `point`, those that may have emitted from it.
+    fn lights_at(&self, point: &Point3) -> Vec<usize>;
+}
+
+/// Lights split into bounded ones and those infinitely far away.
+struct Partition {
+    bounds: Vec<Option<LightBounds>>,
+    infinite: Vec<usize>,
+    bounded: usize,
+}
+
+impl Partition {
+    fn new(lights: &[Box<dyn Light + Send + Sync>]) -> Self {
+        let bounds: Vec<_> = lights.iter().map(|l| l.bounds()).collect();
+        let infinite = (0..lights.len()).filter(|i| bounds[*i].is_none()).collect();
+        let bounded = bounds.iter().filter(|b| b.is_some()).count();
+        Partition {
+            bounds,
+            infinite,
+            bounded,
+        }
+    }
+    /// Probability of picking among the infinite lights.
+    fn p_infinite(&self) -> f64 {
+        let groups = self.infinite.len() + (self.bounded > 0) as usize;
+        if groups == 0 {
+            0.0
+        } else {
+            self.infinite.len() as f64 / groups as f64
+        }
+    }
+    /// Picks an infinite light, or remaps `u` for choosing a bounded one.
+    fn split(&self, u: f64) -> Result<(usize, f64), (f64, f64)> {
+        let p_infinite = self.p_infinite();
+        if u < p_infinite {
+            let n = self.infinite.len();
+            let i = ((u / p_infinite * n as f64) as usize).min(n - 1);
+            Ok((self.infinite[i], p_infinite / n as f64))
+        } else {
+            Err(((u - p_infinite) / (1.0 - p_infinite), 1.0 - p_infinite))
+        }
+    }
+    fn lights_at(&self, point: &Point3) -> Vec<usize> {
+        (0..self.bounds.len())
+            .filter(|i| self.bounds[*i].is_some_and(|b| b.contains(point)))
+            .collect()
+    }
+}
+
+/// Picks bounded lights proportional to their power, regardless of where
+/// the shading point is.
+pub struct PowerLightSampler {
+    partition: Partition,
+    /// Over the bounded lights, in order.
+    table: Option<AliasTable>,
+    /// Index of each light in `table`.
+    slots: Vec<usize>,
+    bounded: Vec<usize>,
+}
+
+impl PowerLightSampler {
+    pub fn new(lights: &[Box<dyn Light + Send + Sync>]) -> Self {
+        let partition = Partition::new(lights);
+        let bounded: Vec<usize> = (0..lights.len())
+            .filter(|i| partition.bounds[*i].is_some())
+            .collect();
+        let mut slots = vec![0; lights.len()];
+        for (slot, light) in bounded.iter().enumerate() {
+            slots[*light] = slot;
+        }
+        let powers: Vec<f64> = bounded
+            .iter()
+            .map(|i| partition.bounds[*i].unwrap().phi)
+            .collect();
+        let table = if powers.is_empty() {
+            None
+        } else {
+            Some(AliasTable::new(&powers))
+        };
+        PowerLightSampler {
+            partition,
+            table,
+            slots,
+            bounded,
+        }
+    }
+}
+
+impl LightSampler for PowerLightSampler {
+    fn sample(&self, _: &Point3, _: &Point3, u: f64) -> Option<(usize, f64)> {
+        match self.partition.split(u) {
+            Ok(infinite) => Some(infinite),
+            Err((u, p_bounded)) => {
+                let (slot, pmf) = self.table.as_ref()?.sample(u);
+                Some((self.bounded[slot], p_bounded * pmf))
+            }
+        }
+    }
+    fn pmf(&self, _: &Point3, _: &Point3, light: usize) -> f64 {
+        let p_infinite = self.partition.p_infinite();
+        match (&self.partition.bounds[light], &self.table) {
+            (None, _) => p_infinite / self.partition.infinite.len() as f64,
+            (Some(_), Some(table)) => (1.0 - p_infinite) * table.pmf(self.slots[light]),
+            (Some(_), None) => 0.0,
+        }
+    }
+    fn lights_at(&self, point: &Point3) -> Vec<usize> {
+        self.partition.lights_at(point)
+    }
+}
+
+enum Node {
+    Leaf(usize),
+    /// The first child directly follows its parent, this is the second.
+    Interior(usize),
+}
+
+/// Bounding volume hierarchy over the bounded lights, traversed
+/// stochastically by the importance of each child at the shading point.
+pub struct BvhLightSampler {
+    partition: Partition,
+    nodes: Vec<(LightBounds, Node)>,
+    /// Root to leaf path of each bounded light, one bit per level with 1
+    /// for the second child, and its length.
+    trails: Vec<(u64, u32)>,
+}
+
+impl BvhLightSampler {
+    pub fn new(lights: &[Box<dyn Light + Send + Sync>]) -> Self {
+        let partition = Partition::new(lights);
+        let mut bounded: Vec<(usize, LightBounds)> = partition
+            .bounds
+            .iter()
+            .enumerate()
+            .filter_map(|(i, b)| b.filter(|b| b.phi > 0.0).map(|b| (i, b)))
+            .collect();
+        let mut sampler = BvhLightSampler {
+            partition,
+            nodes: Vec::new(),
+            trails: vec![(0, 0); lights.len()],
+        };
+        if !bounded.is_empty() {
+            sampler.build(&mut bounded, 0, 0);
+        }
+        sampler
+    }
+
+    /// Median split along the widest axis of the centroids.
+    fn build(&mut self, lights: &mut [(usize, LightBounds)], trail: u64, depth: u32) -> usize {
+        let index = self.nodes.len();
+        if lights.len() == 1 || depth == 63 {
+            let (light, bounds) = lights[0];
+            self.trails[light] = (trail, depth);
+            self.nodes.push((bounds, Node::Leaf(light)));
+            return index;
+        }
+        let centroids: Vec<Point3> = lights.iter().map(|(_, b)| b.centroid()).collect();
+        let extent = |axis: fn(&Point3) -> f64| {
+            let values = centroids.iter().map(axis);
+            values.clone().fold(f64::MIN, f64::max) - values.fold(f64::MAX, f64::min)
+        };
+        let axes: [fn(&Point3) -> f64; 3] = [|p| p.x, |p| p.y, |p| p.z];
+        let axis = *axes
+            .iter()
+            .max_by(|a, b| extent(**a).total_cmp(&extent(**b)))
+            .unwrap();
+        lights.sort_by(|a, b| axis(&a.1.centroid()).total_cmp(&axis(&b.1.centroid())));
+        let bounds = lights
+            .iter()
+            .skip(1)
+            .fold(lights[0].1, |acc, (_, b)| acc.union(b));
+        self.nodes.push((bounds, Node::Interior(0)));
+        let (first, second) = lights.split_at_mut(lights.len() / 2);
+        self.build(first, trail, depth + 1);
+        let second = self.build(second, trail | 1 << depth, depth + 1);
+        self.nodes[index].1 = Node::Interior(second);
+        index
+    }
+
+    /// Probabilities of descending into each child of the interior node `index`.
+    fn child_probabilities(
+        &self,
+        index: usize,
+        second: usize,
+        p: &Point3,
+        n: &Point3,
+    ) -> (f64, f64) {
+        let first = self.nodes[index + 1].0.importance(p, n);
+        let second = self.nodes[second].0.importance(p, n);
+        if first + second == 0.0 {
+            return (0.0, 0.0);
+        }
+        (first / (first + second), second / (first + second))
+    }
+}
+
+impl LightSampler for BvhLightSampler {
+    fn sample(&self, p: &Point3, n: &Point3, u: f64) -> Option<(usize, f64)> {
+        let (mut u, mut pmf) = match self.partition.split(u) {
+            Ok(infinite) => return Some(infinite),
+            Err(bounded) => bounded,
+        };
+        if self.nodes.is_empty() || self.nodes[0].0.importance(p, n) == 0.0 {
+            return None;
+        }
+        let mut index = 0;
+        loop {
+            match self.nodes[index].1 {
+                Node::Leaf(light) => return Some((light, pmf)),
+                Node::Interior(second) => {
+                    let (p_first, p_second) = self.child_probabilities(index, second, p, n);
+                    if p_first + p_second == 0.0 {
+                        return None;
+                    }
+                    if u < p_first {
+                        u /= p_first;
+                        pmf *= p_first;
+                        index += 1;
+                    } else {
+                        u = ((u - p_first) / p_second).min(1.0 - f64::EPSILON);
+                        pmf *= p_second;
+                        index = second;
+                    }
+                }
+            }
+        }
+    }
+    fn pmf(&self, p: &Point3, n: &Point3, light: usize) -> f64 {
+        let p_infinite = self.partition.p_infinite();
+        if self.partition.bounds[light].is_none() {
+            return p_infinite / self.partition.infinite.len() as f64;
+        }
+        if self.nodes.is_empty() || self.nodes[0].0.importance(p, n) == 0.0 {
+            return 0.0;
+        }
+        let (trail, depth) = self.trails[light];
+        let mut pmf = 1.0 - p_infinite;
+        let mut index = 0;
+        for level in 0..depth {
+            let second = match self.nodes[index].1 {
+                Node::Interior(second) => second,
+                Node::Leaf(_) => unreachable!("trail longer than the tree"),
+            };
+            let (p_first, p_second) = self.child_probabilities(index, second, p, n);
+            if trail >> level & 1 == 0 {
+                pmf *= p_first;
+                index += 1;
+            } else {
+                pmf *= p_second;
+                index = second;
+            }
+        }
+        match self.nodes[index].1 {
+            Node::Leaf(l) if l == light => pmf,
+            // Lights without power aren't in the tree.
+            _ => 0.0,
+        }
+    }
+    fn lights_at(&self, point: &Point3) -> Vec<usize> {
+        let mut found = Vec::new();
+        let mut stack = vec![0];
+        while let Some(index) = stack.pop() {
+            let (bounds, node) = match self.nodes.get(index) {
+                Some(node) => node,
+                None => continue,
+            };
+            if !bounds.contains(point) {
+                continue;
+            }
+            match node {
+                Node::Leaf(light) => found.push(*light),
+                Node::Interior(second) => stack.extend_from_slice(&[index + 1, *second]),
+            }
+        }
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::SphereLight;
+
+    fn lights() -> Vec<Box<dyn Light + Send + Sync>> {
+        (0..40)
+            .map(|i| {
+                let center = Point3::new((i % 8) as f64 * 3.0, 0.0, (i / 8) as f64 * 3.0);
+                let emit = Color::new(1.0 + i as f64, 1.0, 1.0);
+                Box::new(SphereLight::new(center, 0.5, emit)) as Box<dyn Light + Send + Sync>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn pmfs_are_consistent() {
+        let lights = lights();
+        let p = Point3::new(4.0, 2.0, 5.0);
+        let n: Point3 = (0, -1, 0).into();
+        let samplers: [Box<dyn LightSampler>; 2] = [
+            Box::new(PowerLightSampler::new(&lights)),
+            Box::new(BvhLightSampler::new(&lights)),
+        ];
+        for sampler in &samplers {
+            let total: f64 = (0..lights.len()).map(|i| sampler.pmf(&p, &n, i)).sum();
+            assert!((total - 1.0).abs() < 1e-9);
+            for k in 0..100 {
+                let (light, pmf) = sampler.sample(&p, &n, (k as f64 + 0.5) / 100.0).unwrap();
+                assert!((pmf - sampler.pmf(&p, &n, light)).abs() < 1e-12);
+            }
+            assert_eq!(sampler.lights_at(&(3.0, 0.5, 3.0).into()), vec![9]);
+        }
+        // The BVH favours the nearby lights.
+        let bvh = BvhLightSampler::new(&lights);
+        assert!(bvh.pmf(&p, &n, 9) > bvh.pmf(&p, &n, 39));
+
+        // A light at a NaN position must not bring the build down.
+        let mut lights = lights;
+        let nan = Point3::new(f64::NAN, 0.0, 0.0);
+        lights.push(Box::new(SphereLight::new(
+            nan,
+            0.5,
+            Color::new(1.0, 1.0, 1.0),
+        )));
+        BvhLightSampler::new(&lights);
+    }
+}
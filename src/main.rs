@@ -1,50 +1,140 @@
-mod camera;
-mod hitrecord;
-mod material;
-mod ray;
-mod sphere;
-mod traits;
-mod vec3;
-
-type Color = Vec3<vec3::Color>;
-type Point3 = Vec3<vec3::Point3>;
-
-use camera::*;
-use hitrecord::*;
-use material::*;
-use rand::{random, Rng};
-use ray::Ray;
+use rand::Rng;
 use rayon::prelude::*;
-use sphere::*;
-use std::fs::File;
-use std::io::BufWriter;
-use std::io::Write;
+use raytracerinoneweekend::{
+    camera::*, environment::*, hitrecord::*, lens::*, light::*, lightsampler::*, material::*,
+    medium::*, output::*, panorama::*, random_range, ray::Ray, shader::*, sky::Sky, sphere::*,
+    stereo::*, traits::*, Color, Point3,
+};
+use std::path::Path;
 use std::sync::Arc;
-use traits::*;
-use vec3::Vec3;
 
-fn ray_color(ray: &Ray, world: &impl Hittable, depth: u32) -> Color {
-    if depth <= 0 {
+struct Scene<H> {
+    world: H,
+    /// Lights sampled by shadow rays, emissive surfaces among them also
+    /// being part of `world`.
+    lights: Vec<Box<dyn Light + Send + Sync>>,
+    light_sampler: Box<dyn LightSampler + Send + Sync>,
+    environment: Box<dyn Environment + Send + Sync>,
+}
+
+/// How the previous vertex sampled a ray, for weighting what it hits
+/// against next-event estimation there.
+#[derive(Clone, Copy, Default)]
+struct Bounce {
+    /// Solid angle density, zero for camera rays, specular bounces and
+    /// medium scattering.
+    pdf: f64,
+    /// Shading normal the light sampler saw.
+    normal: Point3,
+}
+
+/// `media` holds the interiors of the closed surfaces the ray is inside.
+fn ray_color<H: Hittable>(
+    ray: &Ray,
+    scene: &Scene<H>,
+    depth: u32,
+    media: &MediumStack,
+    bounce: Bounce,
+) -> Color {
+    if depth == 0 {
         return Color::default();
     }
+    let world = &scene.world;
     let mut rec = HitRecord::default();
-    if world.hit(ray, 0.001, f64::INFINITY, &mut rec) {
+    let hit = world.hit(ray, 0.001, f64::INFINITY, &mut rec);
+    let mut weight: Color = (1, 1, 1).into();
+    if let Some(medium) = media.current() {
+        let t_max = if hit { rec.t } else { f64::INFINITY };
+        let dir_len = ray.direction().len();
+        if let MediumEvent::Scattered(t) = medium.sample(t_max * dir_len, &mut weight) {
+            // Isotropic phase function.
+            let scattered = Ray::new(ray.at(t / dir_len), Point3::random_unit());
+            return weight * ray_color(&scattered, scene, depth - 1, media, Bounce::default());
+        }
+    }
+    if hit {
+        let m = rec.material.as_ref().unwrap().clone();
+        if !media.is_interface(&m, rec.front_face) {
+            // Surface hidden inside a medium of higher priority, pass through.
+            // It occludes shadow rays, so emission behind it is unweighted.
+            let mut media = media.clone();
+            media.cross(&m, rec.front_face);
+            let continued = Ray::new(rec.p, *ray.direction());
+            return weight * ray_color(&continued, scene, depth - 1, &media, Bounce::default());
+        }
+        rec.exterior_ior = media.exterior_ior(&m, rec.front_face);
+        let mut emitted = m.emitted(ray, &rec);
+        if bounce.pdf > 0.0 && emitted.luminance() != 0.0 {
+            let light_pdf = emitter_pdf(
+                &scene.lights,
+                &*scene.light_sampler,
+                ray.origin(),
+                &bounce.normal,
+                &ray.direction().unit(),
+                &rec,
+            );
+            if light_pdf > 0.0 {
+                emitted = power_heuristic(bounce.pdf, light_pdf) * emitted;
+            }
+        }
+        let normal = rec.normal;
+        let direct = emitted
+            + direct_lighting(
+                &scene.lights,
+                &*scene.light_sampler,
+                world,
+                media.current(),
+                ray,
+                &rec,
+                &*m,
+            )
+            + environment_lighting(&*scene.environment, world, ray, &rec, &*m);
         let mut scattered = Ray::default();
         let mut attenuation = Color::default();
-        let m = rec.material.as_ref().unwrap().clone();
         if m.scatter(ray, &mut rec, &mut attenuation, &mut scattered) {
-            return attenuation * ray_color(&scattered, world, depth - 1);
+            // `pdf` only covers the smooth lobes, a delta sample must not be
+            // weighted against light sampling that can never produce it.
+            let bounce = Bounce {
+                pdf: if rec.specular {
+                    0.0
+                } else {
+                    m.pdf(ray, &rec, scattered.direction())
+                },
+                normal,
+            };
+            let color = if scattered.direction().dot(&rec.geometric_normal) >= 0.0 {
+                ray_color(&scattered, scene, depth - 1, media, bounce)
+            } else {
+                // Crossing the surface enters or leaves the material's medium.
+                let mut media = media.clone();
+                media.cross(&m, rec.front_face);
+                ray_color(&scattered, scene, depth - 1, &media, bounce)
+            };
+            return weight * (direct + attenuation * color);
         }
-        return Color::zeroed();
+        return weight * direct;
     }
     let unit_dir = ray.direction().unit();
-    let t = 0.5 * (unit_dir.y + 1.0);
-    ((1.0 - t) * Color::new(1.0, 1.0, 1.0)) + (t * Color::new(0.5, 0.7, 1.0))
+    let environment = &scene.environment;
+    // Share the environment with its next-event estimation at the previous vertex.
+    let env_pdf = environment.pdf(&unit_dir);
+    let mis = if bounce.pdf > 0.0 && env_pdf > 0.0 {
+        power_heuristic(bounce.pdf, env_pdf)
+    } else {
+        1.0
+    };
+    mis * weight * environment.radiance(&unit_dir)
 }
 
-fn random_scene() -> Vec<Sphere> {
+/// With `glow` some of the small spheres emit light and come back as sphere
+/// lights too. `diffuse` replaces the material of the big brown sphere.
+fn random_scene(
+    glow: bool,
+    diffuse: Option<Arc<dyn Material + Send + Sync>>,
+) -> (Vec<Sphere>, Vec<Box<dyn Light + Send + Sync>>) {
     let mut rng = rand::thread_rng();
     let mut world = Vec::new();
+    let mut lights: Vec<Box<dyn Light + Send + Sync>> = Vec::new();
 
     let ground_material = Arc::new(Lambertian::new((0.5, 0.5, 0.5).into()));
     world.push(Sphere::new((0, -1000, 0).into(), 1000.0, ground_material));
@@ -60,7 +150,12 @@ fn random_scene() -> Vec<Sphere> {
             let dielectric = Arc::new(Dielectric::new(1.5));
 
             if (center - (4.0, 0.2, 0.0).into()).len() > 0.9 {
-                let sphere_material: Arc<dyn Material + Send + Sync> = if choose_mat < 0.8 {
+                let sphere_material: Arc<dyn Material + Send + Sync> = if glow && choose_mat < 0.15
+                {
+                    let emit = 4.0 * Color::random_in_range(0.3, 1.0);
+                    lights.push(Box::new(SphereLight::new(center, 0.2, emit)));
+                    Arc::new(DiffuseLight::new(emit))
+                } else if choose_mat < 0.8 {
                     let albedo = Color::random() * Color::random();
                     Arc::new(Lambertian::new(albedo))
                 } else if choose_mat < 0.95 {
@@ -77,41 +172,190 @@ fn random_scene() -> Vec<Sphere> {
 
     let material1 = Arc::new(Dielectric::new(1.5));
     world.push(Sphere::new((0, 1, 0).into(), 1.0, material1));
-    let material2 = Arc::new(Lambertian::new((0.4, 0.2, 0.1).into()));
+    let material2 = diffuse.unwrap_or_else(|| Arc::new(Lambertian::new((0.4, 0.2, 0.1).into())));
     world.push(Sphere::new((-4, 1, 0).into(), 1.0, material2));
     let material3 = Arc::new(Metal::new((0.7, 0.6, 0.5).into(), 0.0));
     world.push(Sphere::new((4, 1, 0).into(), 1.0, material3));
 
-    world
+    (world, lights)
 }
 
-fn main() {
-    let mut file = BufWriter::with_capacity(8 * 1024 * 1024, File::create("image.ppm").unwrap());
-    const MAX_DEPTH: u32 = 50;
-    const ASPECT_RATIO: f64 = 16.0 / 9.0;
-    const WIDTH: usize = 1200;
-    const HEIGHT: usize = (WIDTH as f64 / ASPECT_RATIO) as usize;
-    const SAMPLES_PER_PIXEL: u32 = 10;
-    file.write_fmt(format_args!("P3\n{} {}\n255\n", WIDTH, HEIGHT))
-        .unwrap();
-
-    let world = random_scene();
+/// The background named on the command line, a gradient by default that is
+/// dark with `glow`.
+fn environment(args: &[String], glow: bool) -> Box<dyn Environment + Send + Sync> {
+    if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("env=")) {
+        let map = EnvironmentMap::load(path).unwrap_or_else(|err| {
+            eprintln!("can't read environment map {}: {}", path, err);
+            std::process::exit(1)
+        });
+        Box::new(map)
+    } else if args.iter().any(|arg| arg == "sky") {
+        // Mid-afternoon sun over a clear sky.
+        let sun = Point3::new(-0.6, 0.5, 0.6);
+        Box::new(Sky::new(sun, 3.0, (0.3, 0.3, 0.3).into()))
+    } else if glow {
+        Box::new(Gradient::new(
+            (0.01, 0.01, 0.02).into(),
+            (0.02, 0.03, 0.06).into(),
+        ))
+    } else {
+        Box::new(Gradient::default())
+    }
+}
 
+/// The camera named on the command line, perspective by default, with the
+/// aspect ratio of the image it makes and the exposure to scale it by.
+fn camera(args: &[String], world: &impl Hittable) -> (Box<dyn Camera + Sync>, f64, f64) {
+    const ASPECT_RATIO: f64 = 16.0 / 9.0;
+    let has = |name: &str| args.iter().any(|arg| arg == name);
     let lookfrom: Point3 = (13, 2, 3).into();
     let lookat: Point3 = (0, 0, 0).into();
     let vup: Point3 = (0, 1, 0).into();
     let dist_to_focus = 10.0;
     let aperture = 0.1;
-    let cam = Camera::new(
-        lookfrom,
-        lookat,
-        vup,
-        Degrees::new(20.0),
-        ASPECT_RATIO,
-        aperture,
-        dist_to_focus,
+    if has("ortho") {
+        let camera = OrthographicCamera::new(lookfrom, lookat, vup, 5.0, ASPECT_RATIO);
+        (Box::new(camera), ASPECT_RATIO, 1.0)
+    } else if has("equirect") {
+        let camera = EquirectangularCamera::new((0, 1, 0).into(), (1, 1, 0).into(), vup);
+        (Box::new(camera), 2.0, 1.0)
+    } else if has("fisheye") {
+        let camera = FisheyeCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees::new(180.0),
+            ASPECT_RATIO,
+            FisheyeProjection::Equisolid,
+        );
+        (Box::new(camera), ASPECT_RATIO, 1.0)
+    } else if has("cubemap") {
+        (Box::new(CubemapCamera::new((0, 1, 0).into())), 6.0, 1.0)
+    } else if let Some(path) = args.iter().find_map(|arg| arg.strip_prefix("lens=")) {
+        let prescription = LensPrescription::load(path).unwrap_or_else(|err| {
+            eprintln!("can't read lens {}: {}", path, err);
+            std::process::exit(1)
+        });
+        let camera = RealisticCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            prescription,
+            43.27,
+            ASPECT_RATIO,
+            dist_to_focus,
+        )
+        .unwrap_or_else(|err| {
+            eprintln!("can't use lens {}: {}", path, err);
+            std::process::exit(1)
+        });
+        (Box::new(camera), ASPECT_RATIO, 1.0)
+    } else if has("stereo") {
+        let layout = StereoLayout::SideBySide;
+        let camera = StereoCamera::perspective(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees::new(20.0),
+            ASPECT_RATIO,
+            0.3,
+            dist_to_focus,
+            layout,
+        );
+        (Box::new(camera), layout.aspect_ratio(ASPECT_RATIO), 1.0)
+    } else if has("ods") {
+        let layout = StereoLayout::OverUnder;
+        let camera = OdsCamera::new(
+            (0, 1, 0).into(),
+            (1, 1, 0).into(),
+            vup,
+            0.064,
+            f64::INFINITY,
+            layout,
+        );
+        (Box::new(camera), layout.aspect_ratio(2.0), 1.0)
+    } else if has("physical") {
+        // A fast portrait lens wide open in bright light, focused on what's
+        // at the center of the frame.
+        let settings = CameraSettings {
+            focal_length: 85.0,
+            f_stop: 1.8,
+            shutter: 1.0 / 8000.0,
+            iso: 100.0,
+            ..Default::default()
+        };
+        let camera = settings
+            .camera(lookfrom, lookat, vup, ASPECT_RATIO, dist_to_focus)
+            .autofocus(world, (0.5, 0.5));
+        (Box::new(camera), ASPECT_RATIO, settings.exposure())
+    } else {
+        let mut camera = PerspectiveCamera::new(
+            lookfrom,
+            lookat,
+            vup,
+            Degrees::new(20.0),
+            ASPECT_RATIO,
+            if has("bokeh") { 0.6 } else { aperture },
+            dist_to_focus,
+        );
+        if has("bokeh") {
+            camera = camera.with_aperture(Aperture::polygon(6, Degrees::new(15.0)));
+        }
+        if has("miniature") {
+            camera = camera.with_tilt(Degrees::new(-8.0), Degrees::new(0.0));
+        }
+        if has("autofocus") {
+            camera = camera.autofocus(world, (0.5, 0.5));
+        }
+        (Box::new(camera), ASPECT_RATIO, 1.0)
+    }
+}
+
+fn main() {
+    const MAX_DEPTH: u32 = 50;
+    const WIDTH: usize = 1200;
+    const SAMPLES_PER_PIXEL: u32 = 10;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let has = |name: &str| args.iter().any(|arg| arg == name);
+
+    // Pick the format before rendering so a bad name fails fast.
+    let path = Path::new(
+        args.iter()
+            .find_map(|arg| arg.strip_prefix("out="))
+            .unwrap_or("image.png"),
     );
-    let results = (0..HEIGHT)
+    let depth = if has("16bit") {
+        BitDepth::Sixteen
+    } else {
+        BitDepth::Eight
+    };
+    let writer = writer_for(path, depth, has("ascii")).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(1)
+    });
+
+    let glow = args.iter().any(|arg| arg == "glow");
+    let shader = args
+        .iter()
+        .find_map(|arg| arg.strip_prefix("shader="))
+        .map(|path| {
+            let graph = ShaderGraph::load(path).unwrap_or_else(|err| {
+                eprintln!("can't read shader graph {}: {}", path, err);
+                std::process::exit(1)
+            });
+            Arc::new(graph) as Arc<dyn Material + Send + Sync>
+        });
+    let (world, lights) = random_scene(glow, shader);
+    let scene = Scene {
+        world,
+        light_sampler: Box::new(BvhLightSampler::new(&lights)),
+        lights,
+        environment: environment(&args, glow),
+    };
+    let (cam, aspect_ratio, exposure) = camera(&args, &scene.world);
+    let height = (WIDTH as f64 / aspect_ratio) as usize;
+
+    let results = (0..height)
         .into_par_iter()
         .rev()
         .map(|j| {
@@ -121,25 +365,36 @@ fn main() {
             for i in 0..WIDTH {
                 let mut pixel_color = Color::zeroed();
                 for _ in 0..SAMPLES_PER_PIXEL {
-                    let u = (i as f64 + rng.gen::<f64>()) / (WIDTH - 1) as f64;
-                    let v = (j as f64 + rng.gen::<f64>()) / (HEIGHT - 1) as f64;
-                    let ray = cam.ray(u, v);
-
-                    pixel_color += ray_color(&ray, &world, MAX_DEPTH);
+                    let u = (i as f64 + rng.gen::<f64>()) / WIDTH as f64;
+                    let v = (j as f64 + rng.gen::<f64>()) / height as f64;
+                    let sample = CameraSample::random((u, v));
+                    let (ray, weight) = match cam.generate_weighted_ray(&sample) {
+                        Some(weighted) => weighted,
+                        None => continue,
+                    };
+                    pixel_color += weight
+                        * ray_color(
+                            &ray,
+                            &scene,
+                            MAX_DEPTH,
+                            &MediumStack::default(),
+                            Bounce::default(),
+                        );
                 }
-                v.push(process_color(pixel_color, SAMPLES_PER_PIXEL));
+                v.push(process_color(exposure * pixel_color, SAMPLES_PER_PIXEL));
             }
             v
         })
         .flatten()
-        .collect::<Vec<(_, _, _)>>();
-    for p in results.iter() {
-        file.write_fmt(format_args!("{} {} {}\n", p.0, p.1, p.2))
-            .unwrap();
+        .collect::<Vec<_>>();
+    if let Err(err) = save(&*writer, path, WIDTH, height, &results) {
+        eprintln!("can't write {}: {}", path.display(), err);
+        std::process::exit(1)
     }
 }
 
-fn process_color(pixel: Color, samples_per_pixel: u32) -> (u32, u32, u32) {
+/// Averages the samples and gamma encodes, leaving quantization to the writer.
+fn process_color(pixel: Color, samples_per_pixel: u32) -> Color {
     let mut r = pixel.x;
     let mut g = pixel.y;
     let mut b = pixel.z;
@@ -149,10 +404,7 @@ fn process_color(pixel: Color, samples_per_pixel: u32) -> (u32, u32, u32) {
     g = (scale * g).sqrt();
     b = (scale * b).sqrt();
 
-    let ir = (clamp(r, 0.0, 0.999) * 256.0) as u32;
-    let ig = (clamp(g, 0.0, 0.999) * 256.0) as u32;
-    let ib = (clamp(b, 0.0, 0.999) * 256.0) as u32;
-    (ir, ig, ib)
+    Color::new(r, g, b)
 }
 
 // fn write_color(stream: &mut impl Write, pixel: Color, samples_per_pixel: u32) {
@@ -162,19 +414,3 @@ fn process_color(pixel: Color, samples_per_pixel: u32) -> (u32, u32, u32) {
 //         .write_fmt(format_args!("{} {} {}\n", ir, ig, ib))
 //         .unwrap();
 // }
-
-fn clamp<T>(val: T, min: T, max: T) -> T
-where
-    T: Copy + std::cmp::PartialOrd,
-{
-    if val < min {
-        min
-    } else if val > max {
-        max
-    } else {
-        val
-    }
-}
-fn random_range(min: f64, max: f64) -> f64 {
-    min + (max - min) * rand::random::<f64>()
-}
@@ -1,4 +1,5 @@
 use super::*;
+use std::sync::Arc;
 
 fn schlick(cosine: f64, ref_idx: f64) -> f64 {
     let r0 = (1.0 - ref_idx) / (1.0 + ref_idx);
@@ -14,6 +15,60 @@ pub trait Material {
         attenuation: &mut Color,
         scattered: &mut Ray,
     ) -> bool;
+    /// BSDF times cosine for light arriving from the world direction `wi`
+    /// and leaving towards `r_in`'s origin. Zero for materials which only
+    /// scatter into discrete directions.
+    fn eval(&self, _r_in: &Ray, _rec: &HitRecord, _wi: &Point3) -> Color {
+        Color::zeroed()
+    }
+    /// Solid angle density with which `scatter` picks `wi`, leaving out
+    /// lobes it flags with `HitRecord::specular`.
+    fn pdf(&self, _r_in: &Ray, _rec: &HitRecord, _wi: &Point3) -> f64 {
+        0.0
+    }
+    /// Participating medium filling closed surfaces made of this material.
+    fn medium(&self) -> Option<&Medium> {
+        None
+    }
+    /// Radiance emitted towards `r_in`'s origin.
+    fn emitted(&self, _r_in: &Ray, _rec: &HitRecord) -> Color {
+        Color::zeroed()
+    }
+    /// Radiance emitted uniformly from the front face, for surfaces that
+    /// can be sampled as lights.
+    fn emission(&self) -> Option<Color> {
+        None
+    }
+}
+
+/// Emits constant radiance from the front face and scatters nothing.
+pub struct DiffuseLight {
+    emit: Color,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self {
+        DiffuseLight { emit }
+    }
+    pub fn emit(&self) -> Color {
+        self.emit
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &mut HitRecord, _: &mut Color, _: &mut Ray) -> bool {
+        false
+    }
+    fn emitted(&self, _: &Ray, rec: &HitRecord) -> Color {
+        if rec.front_face {
+            self.emit
+        } else {
+            Color::zeroed()
+        }
+    }
+    fn emission(&self) -> Option<Color> {
+        Some(self.emit)
+    }
 }
 
 pub struct Lambertian {
@@ -37,6 +92,13 @@ impl Material for Lambertian {
         *attenuation = self.albedo;
         true
     }
+    fn eval(&self, _: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let cosine = wi.unit().dot(&rec.normal).max(0.0);
+        (cosine / std::f64::consts::PI) * self.albedo
+    }
+    fn pdf(&self, _: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        wi.unit().dot(&rec.normal).max(0.0) / std::f64::consts::PI
+    }
 }
 
 pub struct Metal {
@@ -60,17 +122,51 @@ impl Material for Metal {
         let reflected = r_in.direction().unit().reflect(&rec.normal);
         *scattered = Ray::new(rec.p, reflected + self.fuzz * Vec3::random_in_unit_sphere());
         *attenuation = self.albedo;
+        rec.specular = true;
         scattered.direction().dot(&rec.normal) > 0.0
     }
 }
 
 pub struct Dielectric {
     ref_idx: f64,
+    film: Option<ThinFilm>,
+    interior: Medium,
 }
 
 impl Dielectric {
     pub fn new(ref_idx: f64) -> Self {
-        Dielectric { ref_idx }
+        Dielectric {
+            ref_idx,
+            film: None,
+            interior: Medium::new(Color::zeroed(), Color::zeroed()).with_ior(ref_idx),
+        }
+    }
+    /// Colored glass, light keeps `color` of its intensity after traveling
+    /// `distance` inside the material. `distance` must be positive.
+    pub fn with_absorption(mut self, color: Color, distance: f64) -> Self {
+        assert!(distance > 0.0, "absorption distance must be positive");
+        let sigma = |c: f64| -c.max(1e-6).ln() / distance;
+        let sigma_a = Color::new(sigma(color.x), sigma(color.y), sigma(color.z));
+        self.interior = Medium::new(sigma_a, Color::zeroed())
+            .with_ior(self.ref_idx)
+            .with_priority(self.interior.priority());
+        self
+    }
+    /// Where dielectrics overlap, e.g. ice modeled intersecting water, the
+    /// one with the higher priority fills the overlap.
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.interior = self.interior.with_priority(priority);
+        self
+    }
+    /// Wraps the boundary around a scattering interior.
+    pub(crate) fn with_interior(mut self, interior: Medium) -> Self {
+        self.interior = interior.with_ior(self.ref_idx);
+        self
+    }
+    /// Covers the outside of the surface with an interference film.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
     }
 }
 impl Material for Dielectric {
@@ -82,10 +178,11 @@ impl Material for Dielectric {
         scattered: &mut Ray,
     ) -> bool {
         *attenuation = (1, 1, 1).into();
+        rec.specular = true;
         let etai_over_etat = if rec.front_face {
-            1.0 / self.ref_idx
+            rec.exterior_ior / self.ref_idx
         } else {
-            self.ref_idx
+            self.ref_idx / rec.exterior_ior
         };
         let unit_direction = r_in.direction().unit();
 
@@ -96,6 +193,22 @@ impl Material for Dielectric {
             *scattered = Ray::new(rec.p, reflected);
             return true;
         }
+        if let (Some(film), true) = (&self.film, rec.front_face) {
+            // Reflectance differs per channel, pick a branch by its average
+            // and reweight the channels.
+            let reflectance =
+                film.reflectance(cos_theta, &Substrate::Dielectric(self.ref_idx), rec);
+            let reflect_prob = (reflectance.x + reflectance.y + reflectance.z) / 3.0;
+            if random::<f64>() < reflect_prob {
+                *attenuation = reflectance / reflect_prob;
+                *scattered = Ray::new(rec.p, unit_direction.reflect(&rec.normal));
+            } else {
+                let white: Color = (1, 1, 1).into();
+                *attenuation = (white - reflectance) / (1.0 - reflect_prob);
+                *scattered = Ray::new(rec.p, unit_direction.refract(&rec.normal, etai_over_etat));
+            }
+            return true;
+        }
         let reflect_prob = schlick(cos_theta, etai_over_etat);
         if random::<f64>() < reflect_prob {
             let reflected = unit_direction.reflect(&rec.normal);
@@ -107,4 +220,266 @@ impl Material for Dielectric {
         *scattered = Ray::new(rec.p, refracted);
         true
     }
+    fn medium(&self) -> Option<&Medium> {
+        Some(&self.interior)
+    }
+}
+
+/// GGX microfacet conductor with a per-channel complex index of refraction `eta + i*k`.
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: Ggx,
+    film: Option<ThinFilm>,
+    /// Rotation of the anisotropy around the normal in turns, read from the red channel.
+    rotation: Option<Arc<dyn Texture + Send + Sync>>,
+}
+impl Conductor {
+    /// 0 <= `roughness` <= 1
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Conductor {
+            eta,
+            k,
+            distribution: Ggx::new(roughness),
+            film: None,
+            rotation: None,
+        }
+    }
+    /// Brushed metal, `roughness_u` applies along the surface's `dpdu`
+    /// tangent and `roughness_v` across it.
+    pub fn with_anisotropy(mut self, roughness_u: f64, roughness_v: f64) -> Self {
+        self.distribution = Ggx::anisotropic(roughness_u, roughness_v);
+        self
+    }
+    /// Rotates the anisotropy direction, e.g. for radially brushed discs.
+    pub fn with_rotation(mut self, rotation: Arc<dyn Texture + Send + Sync>) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+    /// Shading frame, aligned with the tangent for anisotropic roughness.
+    fn frame(&self, rec: &HitRecord) -> Onb {
+        if self.distribution.is_isotropic() {
+            return Onb::build_from_w(&rec.normal);
+        }
+        let frame = Onb::from_normal_tangent(&rec.normal, &rec.dpdu);
+        match &self.rotation {
+            Some(rotation) => {
+                let angle = 2.0 * std::f64::consts::PI * rotation.value(rec.u, rec.v, &rec.p).x;
+                let (sin, cos) = angle.sin_cos();
+                let tangent = cos * frame.u + sin * frame.v;
+                Onb::from_normal_tangent(&rec.normal, &tangent)
+            }
+            None => frame,
+        }
+    }
+    /// Covers the surface with an interference film, e.g. an oxide layer.
+    pub fn with_thin_film(mut self, film: ThinFilm) -> Self {
+        self.film = Some(film);
+        self
+    }
+    fn fresnel(&self, cos_i: f64, rec: &HitRecord) -> Color {
+        match &self.film {
+            Some(film) => {
+                let substrate = Substrate::Conductor {
+                    eta: self.eta,
+                    k: self.k,
+                };
+                film.reflectance(cos_i, &substrate, rec)
+            }
+            None => fresnel_conductor(cos_i, self.eta, self.k),
+        }
+    }
+    pub fn gold(roughness: f64) -> Self {
+        Conductor::new(
+            (0.143, 0.374, 1.442).into(),
+            (3.983, 2.385, 1.603).into(),
+            roughness,
+        )
+    }
+    pub fn copper(roughness: f64) -> Self {
+        Conductor::new(
+            (0.200, 0.924, 1.102).into(),
+            (3.912, 2.452, 2.142).into(),
+            roughness,
+        )
+    }
+    pub fn aluminum(roughness: f64) -> Self {
+        Conductor::new(
+            (1.657, 0.880, 0.521).into(),
+            (9.224, 6.270, 4.837).into(),
+            roughness,
+        )
+    }
+}
+impl Material for Conductor {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let frame = self.frame(rec);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let m = self.distribution.sample_vndf(&wo);
+        let wi = (-wo).reflect(&m);
+        if wi.z <= 0.0 {
+            return false;
+        }
+        *scattered = Ray::new(rec.p, frame.local(&wi));
+        // With visible normal sampling D and the cosine terms cancel out of f * cos / pdf.
+        *attenuation = (self.distribution.g(&wo, &wi) / self.distribution.g1(&wo))
+            * self.fresnel(wo.dot(&m), rec);
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let frame = self.frame(rec);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&wi.unit());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zeroed();
+        }
+        let m = (wo + wi).unit();
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g(&wo, &wi);
+        (d * g / (4.0 * wo.z)) * self.fresnel(wo.dot(&m), rec)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let frame = self.frame(rec);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&wi.unit());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let m = (wo + wi).unit();
+        self.distribution.pdf_vndf(&wo, &m) / (4.0 * wo.dot(&m))
+    }
+}
+
+/// GGX microfacet dielectric, frosted glass (Walter et al. 2007).
+pub struct RoughDielectric {
+    ref_idx: f64,
+    distribution: Ggx,
+    /// At the smallest roughness GGX is clamped to, the lobes are too
+    /// narrow for `eval` and `pdf` and are treated as mirror and window.
+    smooth: bool,
+}
+impl RoughDielectric {
+    /// 0 <= `roughness` <= 1
+    pub fn new(ref_idx: f64, roughness: f64) -> Self {
+        RoughDielectric {
+            ref_idx,
+            distribution: Ggx::new(roughness),
+            smooth: roughness <= 0.01,
+        }
+    }
+    /// Index of refraction on the far side of the surface over the one `rec`
+    /// is hit from, the outside being `rec.exterior_ior` like for `Dielectric`.
+    fn eta(&self, rec: &HitRecord) -> f64 {
+        if rec.front_face {
+            self.ref_idx / rec.exterior_ior
+        } else {
+            rec.exterior_ior / self.ref_idx
+        }
+    }
+    /// Microfacet normal taking `wo` to `wi` in the local frame, facing
+    /// `wo`, with whether `wi` is reflected. `None` if no microfacet does.
+    fn half_vector(wo: &Point3, wi: &Point3, eta: f64) -> Option<(Point3, bool)> {
+        let reflect = wi.z > 0.0;
+        let m = if reflect { *wo + *wi } else { *wo + eta * *wi };
+        if m.len_squared() == 0.0 {
+            return None;
+        }
+        let m = if m.z < 0.0 { -m.unit() } else { m.unit() };
+        let back_facing = wo.dot(&m) <= 0.0 || (wi.dot(&m) < 0.0) == reflect;
+        if wi.z == 0.0 || back_facing {
+            return None;
+        }
+        Some((m, reflect))
+    }
+}
+impl Material for RoughDielectric {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let etat_over_etai = self.eta(rec);
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let m = self.distribution.sample_vndf(&wo);
+        let reflect_prob = fresnel_dielectric(wo.dot(&m), etat_over_etai);
+        let wi = if random::<f64>() < reflect_prob {
+            let wi = (-wo).reflect(&m);
+            if wi.z <= 0.0 {
+                return false;
+            }
+            wi
+        } else {
+            let wi = (-wo).refract(&m, 1.0 / etat_over_etai);
+            if wi.z >= 0.0 {
+                return false;
+            }
+            wi
+        };
+        *scattered = Ray::new(rec.p, frame.local(&wi));
+        // Fresnel is accounted for by the choice between reflection and refraction.
+        let weight = self.distribution.g(&wo, &wi) / self.distribution.g1(&wo);
+        *attenuation = (weight, weight, weight).into();
+        rec.specular = self.smooth;
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&wi.unit());
+        let eta = self.eta(rec);
+        if self.smooth || wo.z <= 0.0 {
+            return Color::zeroed();
+        }
+        let (m, reflect) = match RoughDielectric::half_vector(&wo, &wi, eta) {
+            Some(h) => h,
+            None => return Color::zeroed(),
+        };
+        let d = self.distribution.d(&m);
+        let g = self.distribution.g(&wo, &wi);
+        let f = fresnel_dielectric(wo.dot(&m), eta);
+        let value = if reflect {
+            d * g * f / (4.0 * wo.z)
+        } else {
+            // Radiance is not rescaled by eta^2 on the way through, as with `Dielectric`.
+            let denom = wo.dot(&m) + eta * wi.dot(&m);
+            eta * eta * wi.dot(&m).abs() * wo.dot(&m) * (1.0 - f) * d * g / (wo.z * denom * denom)
+        };
+        (value, value, value).into()
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&wi.unit());
+        let eta = self.eta(rec);
+        if self.smooth || wo.z <= 0.0 {
+            return 0.0;
+        }
+        let (m, reflect) = match RoughDielectric::half_vector(&wo, &wi, eta) {
+            Some(h) => h,
+            None => return 0.0,
+        };
+        let pdf_m = self.distribution.pdf_vndf(&wo, &m);
+        let f = fresnel_dielectric(wo.dot(&m), eta);
+        if reflect {
+            f * pdf_m / (4.0 * wo.dot(&m))
+        } else {
+            let denom = wo.dot(&m) + eta * wi.dot(&m);
+            (1.0 - f) * pdf_m * eta * eta * wi.dot(&m).abs() / (denom * denom)
+        }
+    }
 }
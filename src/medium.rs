@@ -0,0 +1,226 @@
+//! Homogeneous participating media filling the inside of closed surfaces.
+use super::*;
+use std::sync::Arc;
+
+pub struct Medium {
+    sigma_a: Color,
+    sigma_s: Color,
+    ior: f64,
+    priority: u32,
+}
+
+/// Outcome of tracking a ray segment through a medium.
+pub enum MediumEvent {
+    /// The ray scattered at distance `t` before reaching the surface.
+    Scattered(f64),
+    /// The ray reached the surface hit that bounded the segment.
+    Passed,
+}
+
+impl Medium {
+    /// Per-channel absorption and scattering coefficients per unit length.
+    pub fn new(sigma_a: Color, sigma_s: Color) -> Self {
+        Medium {
+            sigma_a,
+            sigma_s,
+            ior: 1.0,
+            priority: 0,
+        }
+    }
+    /// Medium with the given per-channel mean free path and single scattering albedo.
+    pub fn from_albedo(mean_free_path: Color, albedo: Color) -> Self {
+        let sigma_t = Color::new(
+            1.0 / mean_free_path.x,
+            1.0 / mean_free_path.y,
+            1.0 / mean_free_path.z,
+        );
+        Medium::new(
+            (Color::new(1.0, 1.0, 1.0) - albedo) * sigma_t,
+            albedo * sigma_t,
+        )
+    }
+    /// Index of refraction of the bounding surface's interior.
+    pub fn with_ior(mut self, ior: f64) -> Self {
+        self.ior = ior;
+        self
+    }
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.priority = priority;
+        self
+    }
+    pub fn ior(&self) -> f64 {
+        self.ior
+    }
+    pub fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn sigma_t(&self) -> Color {
+        self.sigma_a + self.sigma_s
+    }
+    /// Fraction of light left after traveling `distance` through the medium.
+    pub fn transmittance(&self, distance: f64) -> Color {
+        let sigma_t = self.sigma_t();
+        Color::new(
+            (-sigma_t.x * distance).exp(),
+            (-sigma_t.y * distance).exp(),
+            (-sigma_t.z * distance).exp(),
+        )
+    }
+
+    /// Samples a free-flight distance along a segment of length `t_max`,
+    /// multiplying `weight` by the throughput of the event. Distances are
+    /// drawn for a uniformly chosen channel and weighted by the average
+    /// density over all channels, so chromatic media stay unbiased.
+    pub fn sample(&self, t_max: f64, weight: &mut Color) -> MediumEvent {
+        let sigma_t = self.sigma_t();
+        if self.sigma_s.x == 0.0 && self.sigma_s.y == 0.0 && self.sigma_s.z == 0.0 {
+            // Purely absorbing, nothing to sample.
+            *weight *= self.transmittance(t_max);
+            return MediumEvent::Passed;
+        }
+        let channel = match random::<f64>() {
+            x if x < 1.0 / 3.0 => sigma_t.x,
+            x if x < 2.0 / 3.0 => sigma_t.y,
+            _ => sigma_t.z,
+        };
+        let t = if channel > 0.0 {
+            -(1.0 - random::<f64>()).ln() / channel
+        } else {
+            f64::INFINITY
+        };
+        let transmittance = self.transmittance(t.min(t_max));
+        let avg = |c: Color| (c.x + c.y + c.z) / 3.0;
+        if t < t_max {
+            let pdf = avg(sigma_t * transmittance);
+            *weight *= self.sigma_s * transmittance / pdf;
+            MediumEvent::Scattered(t)
+        } else {
+            *weight *= transmittance / avg(transmittance);
+            MediumEvent::Passed
+        }
+    }
+}
+
+/// Materials whose interiors a ray is currently inside, once per closed
+/// surface entered, so objects sharing a material may overlap.
+///
+/// Overlapping closed surfaces are resolved with priorities (Schmidt and
+/// Budge 2002): the overlap belongs to the medium with the highest priority,
+/// surfaces of lower priority media inside it are ignored.
+#[derive(Clone, Default)]
+pub struct MediumStack {
+    entries: Vec<Arc<dyn Material + Send + Sync>>,
+}
+
+impl MediumStack {
+    fn position(&self, material: &Arc<dyn Material + Send + Sync>) -> Option<usize> {
+        self.entries.iter().position(|m| Arc::ptr_eq(m, material))
+    }
+    /// The highest priority medium, ignoring the one being left through a
+    /// surface made of `leaving`.
+    fn highest<'a>(
+        &'a self,
+        leaving: Option<&Arc<dyn Material + Send + Sync>>,
+    ) -> Option<&'a Medium> {
+        let skip = leaving.and_then(|m| self.position(m));
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| Some(*i) != skip)
+            .filter_map(|(_, m)| m.medium())
+            .fold(None, |best: Option<&Medium>, m| match best {
+                Some(b) if b.priority >= m.priority => Some(b),
+                _ => Some(m),
+            })
+    }
+    /// The medium the ray travels through.
+    pub fn current(&self) -> Option<&Medium> {
+        self.highest(None)
+    }
+    /// Whether a surface made of `material`, hit from the outside if
+    /// `entering`, is a real interface between media rather than one hidden
+    /// inside a medium of higher priority.
+    pub fn is_interface(&self, material: &Arc<dyn Material + Send + Sync>, entering: bool) -> bool {
+        match (material.medium(), self.outside(material, entering)) {
+            (Some(m), Some(outer)) => m.priority >= outer.priority,
+            _ => true,
+        }
+    }
+    /// Index of refraction on the other side of a surface made of `material`
+    /// from its interior.
+    pub fn exterior_ior(&self, material: &Arc<dyn Material + Send + Sync>, entering: bool) -> f64 {
+        self.outside(material, entering).map_or(1.0, |m| m.ior)
+    }
+    fn outside(
+        &self,
+        material: &Arc<dyn Material + Send + Sync>,
+        entering: bool,
+    ) -> Option<&Medium> {
+        self.highest(if entering { None } else { Some(material) })
+    }
+    /// Enters `material`'s interior through a front face, or leaves one
+    /// surface of it through a back face.
+    pub fn cross(&mut self, material: &Arc<dyn Material + Send + Sync>, entering: bool) {
+        if material.medium().is_none() {
+            return;
+        }
+        if entering {
+            self.entries.push(Arc::clone(material));
+        } else if let Some(i) = self.position(material) {
+            self.entries.remove(i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_throughput_is_transmittance() {
+        let medium = Medium::from_albedo((1.0, 0.5, 0.25).into(), (0.9, 0.9, 0.9).into());
+        let n = 200_000;
+        let mut sum = Color::zeroed();
+        for _ in 0..n {
+            let mut weight: Color = (1, 1, 1).into();
+            if let MediumEvent::Passed = medium.sample(1.0, &mut weight) {
+                sum += weight;
+            }
+        }
+        let expected = medium.transmittance(1.0);
+        let estimate = sum / n as f64;
+        assert!((estimate - expected).len() < 0.01);
+    }
+
+    #[test]
+    fn higher_priority_hides_nested_surfaces() {
+        let water: Arc<dyn Material + Send + Sync> =
+            Arc::new(Dielectric::new(1.33).with_priority(1));
+        let ice: Arc<dyn Material + Send + Sync> = Arc::new(Dielectric::new(1.31).with_priority(2));
+        let mut stack = MediumStack::default();
+        stack.cross(&water, true);
+        assert!(stack.is_interface(&ice, true));
+        assert_eq!(stack.exterior_ior(&ice, true), 1.33);
+        stack.cross(&ice, true);
+        assert_eq!(stack.current().unwrap().ior(), 1.31);
+        // The water surface inside the ice is not a real interface.
+        assert!(!stack.is_interface(&water, false));
+        stack.cross(&ice, false);
+        assert_eq!(stack.exterior_ior(&water, false), 1.0);
+
+        // Two overlapping objects sharing a material: leaving the first
+        // inside the second stays in glass.
+        let glass: Arc<dyn Material + Send + Sync> = Arc::new(Dielectric::new(1.5));
+        let mut stack = MediumStack::default();
+        stack.cross(&glass, true);
+        assert_eq!(stack.exterior_ior(&glass, true), 1.5);
+        stack.cross(&glass, true);
+        assert_eq!(stack.exterior_ior(&glass, false), 1.5);
+        stack.cross(&glass, false);
+        assert_eq!(stack.current().unwrap().ior(), 1.5);
+        assert_eq!(stack.exterior_ior(&glass, false), 1.0);
+        stack.cross(&glass, false);
+        assert!(stack.current().is_none());
+    }
+}
@@ -0,0 +1,144 @@
+//! Measured isotropic BRDFs in the MERL `.binary` format (Matusik et al. 2003).
+//!
+//! The file holds three little-endian `i32` dimensions followed by the red,
+//! green and blue tables of `f64` samples, indexed by the half and
+//! difference angles of Rusinkiewicz's parametrization.
+use super::*;
+use std::f64::consts::PI;
+use std::io;
+
+const THETA_H: usize = 90;
+const THETA_D: usize = 90;
+const PHI_D: usize = 180;
+const SAMPLES: usize = THETA_H * THETA_D * PHI_D;
+const SCALE: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+pub struct Merl {
+    /// Red, green and blue tables, one after the other.
+    data: Vec<f64>,
+}
+
+impl Merl {
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        Merl::from_bytes(&std::fs::read(path)?)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what);
+        if bytes.len() < 12 {
+            return Err(invalid("MERL header is truncated"));
+        }
+        let dim = |i: usize| {
+            let mut b = [0u8; 4];
+            b.copy_from_slice(&bytes[4 * i..4 * i + 4]);
+            i32::from_le_bytes(b)
+        };
+        let expected = [THETA_H, THETA_D, PHI_D];
+        if (0..3).any(|i| dim(i) < 0 || dim(i) as usize != expected[i]) {
+            return Err(invalid("unexpected MERL table dimensions"));
+        }
+        let raster = &bytes[12..];
+        if raster.len() != 3 * SAMPLES * 8 {
+            return Err(invalid("MERL table size doesn't match its header"));
+        }
+        let data = raster
+            .chunks(8)
+            .map(|c| {
+                let mut b = [0u8; 8];
+                b.copy_from_slice(c);
+                f64::from_le_bytes(b)
+            })
+            .collect();
+        Ok(Merl { data })
+    }
+
+    /// BRDF value for directions in the local shading frame.
+    fn brdf(&self, wo: &Point3, wi: &Point3) -> Color {
+        let h = (*wo + *wi).unit();
+        let theta_h = clamp(h.z, -1.0, 1.0).acos();
+        let phi_h = h.y.atan2(h.x);
+        let diff = wi
+            .rotate(&(0, 0, 1).into(), -phi_h)
+            .rotate(&(0, 1, 0).into(), -theta_h);
+        let theta_d = clamp(diff.z, -1.0, 1.0).acos();
+        let mut phi_d = diff.y.atan2(diff.x);
+        // Reciprocity, only half of the phi range is stored.
+        if phi_d < 0.0 {
+            phi_d += PI;
+        }
+
+        // Theta half is sampled non-linearly to resolve the specular peak.
+        let theta_h_deg = theta_h / (PI / 2.0) * THETA_H as f64;
+        let i_h = ((theta_h_deg * THETA_H as f64).sqrt() as usize).min(THETA_H - 1);
+        let i_d = ((theta_d / (PI / 2.0) * THETA_D as f64) as usize).min(THETA_D - 1);
+        let i_p = ((phi_d / PI * PHI_D as f64) as usize).min(PHI_D - 1);
+        let index = i_p + i_d * PHI_D + i_h * PHI_D * THETA_D;
+
+        // Negative entries mark missing measurements.
+        let value = |c: usize| (self.data[index + c * SAMPLES] * SCALE[c]).max(0.0);
+        Color::new(value(0), value(1), value(2))
+    }
+}
+
+impl Material for Merl {
+    /// Cosine weighted sampling, measured data has no analytic importance function.
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&(rec.normal + Vec3::random_unit()).unit());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return false;
+        }
+        *scattered = Ray::new(rec.p, frame.local(&wi));
+        *attenuation = PI * self.brdf(&wo, &wi);
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        let wi = frame.to_local(&wi.unit());
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zeroed();
+        }
+        wi.z * self.brdf(&wo, &wi)
+    }
+    fn pdf(&self, _: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        wi.unit().dot(&rec.normal).max(0.0) / PI
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn constant_table_is_lambertian() {
+        let mut bytes = Vec::with_capacity(12 + 3 * SAMPLES * 8);
+        for d in &[THETA_H, THETA_D, PHI_D] {
+            bytes.extend_from_slice(&(*d as i32).to_le_bytes());
+        }
+        for scale in &SCALE {
+            let value = 0.5 / PI / scale;
+            for _ in 0..SAMPLES {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        let merl = Merl::from_bytes(&bytes).unwrap();
+        let wo = Point3::new(0.3, -0.2, 0.9).unit();
+        let wi = Point3::new(-0.5, 0.1, 0.4).unit();
+        let f = merl.brdf(&wo, &wi);
+        for c in &[f.x, f.y, f.z] {
+            assert!((c - 0.5 / PI).abs() < 1e-12);
+        }
+        assert!(Merl::from_bytes(&bytes[..100]).is_err());
+        let mut corrupt = bytes.clone();
+        corrupt[..4].copy_from_slice(&(-1i32).to_le_bytes());
+        corrupt[4..8].copy_from_slice(&i32::MAX.to_le_bytes());
+        assert!(Merl::from_bytes(&corrupt).is_err());
+    }
+}
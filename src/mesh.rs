@@ -0,0 +1,261 @@
+//! Indexed triangle meshes.
+use super::*;
+use crate::light::MeshLight;
+use std::sync::Arc;
+
+pub struct Mesh {
+    positions: Vec<Point3>,
+    normals: Vec<Point3>,
+    uvs: Vec<(f64, f64)>,
+    /// Per-vertex tangent and the handedness of the bitangent, `B = w * (N x T)`.
+    tangents: Vec<(Point3, f64)>,
+    indices: Vec<[usize; 3]>,
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Mesh {
+    /// Missing normals are taken from the faces, missing texture
+    /// coordinates default to zero. Panics if an index is out of range or
+    /// the vertex attributes differ in length.
+    pub fn new(
+        positions: Vec<Point3>,
+        normals: Option<Vec<Point3>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        indices: Vec<[usize; 3]>,
+        material: Arc<dyn Material + Send + Sync>,
+    ) -> Self {
+        assert!(
+            indices.iter().flatten().all(|i| *i < positions.len()),
+            "mesh index out of range"
+        );
+        assert!(normals.as_ref().is_none_or(|n| n.len() == positions.len()));
+        assert!(uvs.as_ref().is_none_or(|uv| uv.len() == positions.len()));
+        let normals = normals.unwrap_or_else(|| face_normals(&positions, &indices));
+        let uvs = uvs.unwrap_or_else(|| vec![(0.0, 0.0); positions.len()]);
+        let tangents = tangent_frames(&positions, &normals, &uvs, &indices);
+        Mesh {
+            positions,
+            normals,
+            uvs,
+            tangents,
+            indices,
+            material,
+        }
+    }
+    /// The light to sample alongside the triangles if the material is a
+    /// `DiffuseLight`. Scenes must add it to their lights for the mesh to be
+    /// sampled as one; otherwise its emission is only found by rays that
+    /// happen to hit it.
+    pub fn light(&self) -> Option<MeshLight> {
+        let emit = self.material.emission()?;
+        let triangles = self
+            .indices
+            .iter()
+            .map(|[a, b, c]| [self.positions[*a], self.positions[*b], self.positions[*c]])
+            .collect();
+        MeshLight::new(triangles, emit)
+    }
+    pub fn triangles(self) -> Vec<Triangle> {
+        let mesh = Arc::new(self);
+        (0..mesh.indices.len())
+            .map(|index| Triangle {
+                mesh: Arc::clone(&mesh),
+                index,
+            })
+            .collect()
+    }
+}
+
+/// Area weighted vertex normals.
+fn face_normals(positions: &[Point3], indices: &[[usize; 3]]) -> Vec<Point3> {
+    let mut normals = vec![Point3::zeroed(); positions.len()];
+    for [a, b, c] in indices {
+        let n = (positions[*b] - positions[*a]).cross(&(positions[*c] - positions[*a]));
+        for i in &[*a, *b, *c] {
+            normals[*i] += n;
+        }
+    }
+    normals.iter().map(|n| n.unit()).collect()
+}
+
+/// Per-vertex tangent frames in the spirit of MikkTSpace: face tangents are
+/// derived from the texture coordinate gradients, accumulated weighted by
+/// the corner angle, then orthogonalized against the vertex normal.
+fn tangent_frames(
+    positions: &[Point3],
+    normals: &[Point3],
+    uvs: &[(f64, f64)],
+    indices: &[[usize; 3]],
+) -> Vec<(Point3, f64)> {
+    let mut tangents = vec![Point3::zeroed(); positions.len()];
+    let mut bitangents = vec![Point3::zeroed(); positions.len()];
+    for face in indices {
+        let [a, b, c] = *face;
+        let e1 = positions[b] - positions[a];
+        let e2 = positions[c] - positions[a];
+        let (du1, dv1) = (uvs[b].0 - uvs[a].0, uvs[b].1 - uvs[a].1);
+        let (du2, dv2) = (uvs[c].0 - uvs[a].0, uvs[c].1 - uvs[a].1);
+        let det = du1 * dv2 - du2 * dv1;
+        if det.abs() < 1e-12 {
+            continue;
+        }
+        let t = (dv2 * e1 - dv1 * e2) / det;
+        let bt = (du1 * e2 - du2 * e1) / det;
+        for k in 0..3 {
+            let i = face[k];
+            let p = positions[i];
+            let to_next = (positions[face[(k + 1) % 3]] - p).unit();
+            let to_prev = (positions[face[(k + 2) % 3]] - p).unit();
+            let angle = clamp(to_next.dot(&to_prev), -1.0, 1.0).acos();
+            tangents[i] += angle * t;
+            bitangents[i] += angle * bt;
+        }
+    }
+    normals
+        .iter()
+        .zip(tangents.iter().zip(bitangents.iter()))
+        .map(|(n, (t, b))| {
+            let t = *t - n.dot(t) * *n;
+            if t.len_squared() < 1e-24 {
+                // No usable texture coordinates, any frame will do.
+                return (Onb::build_from_w(n).u, 1.0);
+            }
+            let w = if n.cross(&t).dot(b) < 0.0 { -1.0 } else { 1.0 };
+            (t.unit(), w)
+        })
+        .collect()
+}
+
+pub struct Triangle {
+    mesh: Arc<Mesh>,
+    index: usize,
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let mesh = &self.mesh;
+        let [a, b, c] = mesh.indices[self.index];
+        let (p0, p1, p2) = (mesh.positions[a], mesh.positions[b], mesh.positions[c]);
+        // Möller-Trumbore.
+        let e1 = p1 - p0;
+        let e2 = p2 - p0;
+        let pvec = ray.direction().cross(&e2);
+        let det = e1.dot(&pvec);
+        if det.abs() < 1e-12 {
+            return false;
+        }
+        let inv_det = 1.0 / det;
+        let tvec = *ray.origin() - p0;
+        let b1 = tvec.dot(&pvec) * inv_det;
+        if !(0.0..=1.0).contains(&b1) {
+            return false;
+        }
+        let qvec = tvec.cross(&e1);
+        let b2 = ray.direction().dot(&qvec) * inv_det;
+        if b2 < 0.0 || b1 + b2 > 1.0 {
+            return false;
+        }
+        let t = e2.dot(&qvec) * inv_det;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+        let b0 = 1.0 - b1 - b2;
+        let lerp = |x: Point3, y: Point3, z: Point3| b0 * x + b1 * y + b2 * z;
+
+        rec.t = t;
+        rec.p = ray.at(t);
+        let (uv0, uv1, uv2) = (mesh.uvs[a], mesh.uvs[b], mesh.uvs[c]);
+        rec.u = b0 * uv0.0 + b1 * uv1.0 + b2 * uv2.0;
+        rec.v = b0 * uv0.1 + b1 * uv1.1 + b2 * uv2.1;
+
+        let mut n = lerp(mesh.normals[a], mesh.normals[b], mesh.normals[c]).unit();
+        if n.dot(&e1.cross(&e2)) < 0.0 {
+            // Shading normals point to the same side as the winding.
+            n = -n;
+        }
+        let (t0, w) = mesh.tangents[a];
+        let tangent = lerp(t0, mesh.tangents[b].0, mesh.tangents[c].0);
+        let tangent = (tangent - n.dot(&tangent) * n).unit();
+        // The smooth frame scaled to the face's texture space derivatives,
+        // so bump maps are as steep as on other shapes.
+        let (du1, dv1) = (uv1.0 - uv0.0, uv1.1 - uv0.1);
+        let (du2, dv2) = (uv2.0 - uv0.0, uv2.1 - uv0.1);
+        let det = du1 * dv2 - du2 * dv1;
+        let (su, sv) = if det.abs() < 1e-12 {
+            (1.0, 1.0)
+        } else {
+            (
+                ((dv2 * e1 - dv1 * e2) / det).len(),
+                ((du1 * e2 - du2 * e1) / det).len(),
+            )
+        };
+        rec.dpdu = su * tangent;
+        rec.dpdv = (w * sv) * n.cross(&tangent);
+        rec.set_face_normal(ray, &n);
+        let face = e1.cross(&e2).unit();
+        rec.geometric_normal = if face.dot(ray.direction()) < 0.0 {
+            face
+        } else {
+            -face
+        };
+        rec.material = Some(Arc::clone(&mesh.material));
+        rec.primitive = self.index;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tangents_follow_texture_coordinates() {
+        let positions = vec![
+            (0, 0, 0).into(),
+            (1, 0, 0).into(),
+            (1, 1, 0).into(),
+            (0, 1, 0).into(),
+        ];
+        // Mirrored along v, the bitangent must flip.
+        let uvs = vec![(0.0, 1.0), (1.0, 1.0), (1.0, 0.0), (0.0, 0.0)];
+        let material = Arc::new(Lambertian::new((0.5, 0.5, 0.5).into()));
+        let mesh = Mesh::new(
+            positions,
+            None,
+            Some(uvs),
+            vec![[0, 1, 2], [0, 2, 3]],
+            material,
+        );
+        for (t, w) in &mesh.tangents {
+            assert!((t.x - 1.0).abs() < 1e-9);
+            assert_eq!(*w, -1.0);
+        }
+
+        let triangles = mesh.triangles();
+        let ray = Ray::new((0.7, 0.2, 1.0).into(), (0, 0, -1).into());
+        let mut rec = HitRecord::default();
+        assert!(triangles.hit(&ray, 0.0, f64::INFINITY, &mut rec));
+        assert!((rec.u - 0.7).abs() < 1e-9 && (rec.v - 0.8).abs() < 1e-9);
+        assert!((rec.dpdv.y + 1.0).abs() < 1e-9);
+
+        // Texture space covers only half of the quad along u.
+        let positions = vec![
+            (0, 0, 0).into(),
+            (1, 0, 0).into(),
+            (1, 1, 0).into(),
+            (0, 1, 0).into(),
+        ];
+        let uvs = vec![(0.0, 0.0), (0.5, 0.0), (0.5, 1.0), (0.0, 1.0)];
+        let material = Arc::new(Lambertian::new((0.5, 0.5, 0.5).into()));
+        let triangles = Mesh::new(
+            positions,
+            None,
+            Some(uvs),
+            vec![[0, 1, 2], [0, 2, 3]],
+            material,
+        )
+        .triangles();
+        assert!(triangles.hit(&ray, 0.0, f64::INFINITY, &mut rec));
+        assert!((rec.dpdu.x - 2.0).abs() < 1e-9 && (rec.dpdv.y - 1.0).abs() < 1e-9);
+    }
+}
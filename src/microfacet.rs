@@ -0,0 +1,204 @@
+//! Trowbridge-Reitz (GGX) microfacet distribution and Fresnel terms.
+//!
+//! All directions are in the local shading frame, where the normal is `+z`.
+use super::*;
+use std::f64::consts::PI;
+
+pub struct Ggx {
+    /// Roughness along the tangent (`x`) and bitangent (`y`) directions.
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+/// Perceptual roughness in [0, 1] to `alpha = roughness^2`.
+fn roughness_to_alpha(roughness: f64) -> f64 {
+    let r = clamp(roughness, 0.0, 1.0);
+    f64::max(r * r, 1e-4)
+}
+
+impl Ggx {
+    /// `roughness` is perceptual roughness in [0, 1], `alpha = roughness^2`.
+    pub fn new(roughness: f64) -> Self {
+        Ggx::anisotropic(roughness, roughness)
+    }
+    /// Separate perceptual roughness along the tangent and the bitangent.
+    pub fn anisotropic(roughness_x: f64, roughness_y: f64) -> Self {
+        Ggx {
+            alpha_x: roughness_to_alpha(roughness_x),
+            alpha_y: roughness_to_alpha(roughness_y),
+        }
+    }
+    pub fn is_isotropic(&self) -> bool {
+        self.alpha_x == self.alpha_y
+    }
+    /// Normal distribution function.
+    pub fn d(&self, m: &Point3) -> f64 {
+        if m.z <= 0.0 {
+            return 0.0;
+        }
+        let x = m.x / self.alpha_x;
+        let y = m.y / self.alpha_y;
+        let e = x * x + y * y + m.z * m.z;
+        1.0 / (PI * self.alpha_x * self.alpha_y * e * e)
+    }
+    /// Smith's auxiliary function.
+    pub fn lambda(&self, w: &Point3) -> f64 {
+        let cos2 = w.z * w.z;
+        if cos2 == 0.0 {
+            return f64::INFINITY;
+        }
+        let x = w.x * self.alpha_x;
+        let y = w.y * self.alpha_y;
+        (-1.0 + (1.0 + (x * x + y * y) / cos2).sqrt()) / 2.0
+    }
+    /// Masking function.
+    pub fn g1(&self, w: &Point3) -> f64 {
+        1.0 / (1.0 + self.lambda(w))
+    }
+    /// Height-correlated masking-shadowing function.
+    pub fn g(&self, wo: &Point3, wi: &Point3) -> f64 {
+        1.0 / (1.0 + self.lambda(wo) + self.lambda(wi))
+    }
+    /// Samples a microfacet normal from the distribution of normals visible
+    /// from `wo` (Heitz 2018). `wo` must be in the upper hemisphere.
+    pub fn sample_vndf(&self, wo: &Point3) -> Point3 {
+        let vh = Point3::new(self.alpha_x * wo.x, self.alpha_y * wo.y, wo.z).unit();
+        let lensq = vh.x * vh.x + vh.y * vh.y;
+        let t1 = if lensq > 0.0 {
+            Point3::new(-vh.y, vh.x, 0.0) / lensq.sqrt()
+        } else {
+            Point3::new(1.0, 0.0, 0.0)
+        };
+        let t2 = vh.cross(&t1);
+
+        let r = random::<f64>().sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+        let p1 = r * phi.cos();
+        let p2 = r * phi.sin();
+        let s = 0.5 * (1.0 + vh.z);
+        let p2 = (1.0 - s) * (1.0 - p1 * p1).sqrt() + s * p2;
+
+        let nh = p1 * t1 + p2 * t2 + (1.0 - p1 * p1 - p2 * p2).max(0.0).sqrt() * vh;
+        Point3::new(self.alpha_x * nh.x, self.alpha_y * nh.y, nh.z.max(0.0)).unit()
+    }
+    /// Density of `sample_vndf` over microfacet normals.
+    pub fn pdf_vndf(&self, wo: &Point3, m: &Point3) -> f64 {
+        if wo.z <= 0.0 {
+            return 0.0;
+        }
+        self.g1(wo) * wo.dot(m).max(0.0) * self.d(m) / wo.z
+    }
+}
+
+/// Unpolarized Fresnel reflectance of a dielectric interface,
+/// `eta` is the relative index of refraction `eta_t / eta_i`.
+pub fn fresnel_dielectric(cos_i: f64, eta: f64) -> f64 {
+    let cos_i = clamp(cos_i, 0.0, 1.0);
+    let sin2_t = (1.0 - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1.0 {
+        return 1.0;
+    }
+    let cos_t = (1.0 - sin2_t).sqrt();
+    let rs = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    let rp = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    (rs * rs + rp * rp) / 2.0
+}
+
+/// Unpolarized Fresnel reflectance of a conductor with complex index of
+/// refraction `eta + i*k`, evaluated per channel.
+pub fn fresnel_conductor(cos_i: f64, eta: Color, k: Color) -> Color {
+    let cos_i = clamp(cos_i, 0.0, 1.0);
+    let f = |eta: f64, k: f64| {
+        let c2 = cos_i * cos_i;
+        let s2 = 1.0 - c2;
+        let t0 = eta * eta - k * k - s2;
+        let a2_plus_b2 = (t0 * t0 + 4.0 * eta * eta * k * k).sqrt();
+        let t1 = a2_plus_b2 + c2;
+        let a = (0.5 * (a2_plus_b2 + t0)).max(0.0).sqrt();
+        let t2 = 2.0 * cos_i * a;
+        let rs = (t1 - t2) / (t1 + t2);
+        let t3 = c2 * a2_plus_b2 + s2 * s2;
+        let t4 = t2 * s2;
+        let rp = rs * (t3 - t4) / (t3 + t4);
+        0.5 * (rs + rp)
+    };
+    Color::new(f(eta.x, k.x), f(eta.y, k.y), f(eta.z, k.z))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ggx_projected_area_is_one() {
+        for ggx in &[Ggx::new(0.5), Ggx::anisotropic(0.3, 0.8)] {
+            let n = 512;
+            let (d_theta, d_phi) = (PI / 2.0 / n as f64, 2.0 * PI / n as f64);
+            let mut sum = 0.0;
+            for i in 0..n {
+                let theta = (i as f64 + 0.5) * d_theta;
+                for j in 0..n {
+                    let phi = (j as f64 + 0.5) * d_phi;
+                    let m = Point3::new(
+                        theta.sin() * phi.cos(),
+                        theta.sin() * phi.sin(),
+                        theta.cos(),
+                    );
+                    sum += ggx.d(&m) * theta.cos() * theta.sin() * d_theta * d_phi;
+                }
+            }
+            assert!((sum - 1.0).abs() < 1e-3);
+        }
+    }
+
+    #[test]
+    fn conductor_without_absorption_is_dielectric() {
+        for &cos_i in &[1.0, 0.7, 0.2] {
+            let c = fresnel_conductor(cos_i, (1.5, 1.5, 1.5).into(), Color::zeroed());
+            assert!((c.x - fresnel_dielectric(cos_i, 1.5)).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn vndf_samples_face_the_viewer() {
+        let ggx = Ggx::new(0.8);
+        let wo = Point3::new(0.6, 0.0, 0.8);
+        for _ in 0..1000 {
+            let m = ggx.sample_vndf(&wo);
+            assert!(wo.dot(&m) >= 0.0);
+            assert!(m.z >= 0.0);
+        }
+    }
+
+    #[test]
+    fn rough_glass_weight_matches_eval_over_pdf() {
+        let glass = RoughDielectric::new(1.5, 0.4);
+        let r_in = Ray::new((0.3, 1.0, 0.0).into(), (-0.3, -1.0, 0.0).into());
+        for outward in &[Point3::new(0.0, 1.0, 0.0), Point3::new(0.0, -1.0, 0.0)] {
+            let mut rec = HitRecord::default();
+            rec.set_face_normal(&r_in, outward);
+            for _ in 0..200 {
+                let mut attenuation = Color::zeroed();
+                let mut scattered = Ray::default();
+                if glass.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered) {
+                    assert!(!rec.specular);
+                    let wi = scattered.direction();
+                    let expected = glass.eval(&r_in, &rec, wi) / glass.pdf(&r_in, &rec, wi);
+                    assert!((attenuation - expected).len() < 1e-6);
+                }
+            }
+        }
+
+        // Immersed in a liquid of the same index the surface disappears.
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        rec.exterior_ior = 1.5;
+        for _ in 0..100 {
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            assert!(glass.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered));
+            let through = scattered.direction().unit() - r_in.direction().unit();
+            assert!(through.len() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! Stochastic blends of two materials, e.g. a dirt mask over metal or a
+//! glossy layer over diffuse without a dedicated struct.
+use super::*;
+use std::sync::Arc;
+
+/// Probability of picking the second material of a `Mix`.
+pub enum MixWeight {
+    Constant(f64),
+    /// Read from the red channel of the texture at the hit.
+    Textured(Arc<dyn Texture + Send + Sync>),
+    /// Dielectric Fresnel reflectance with the given IOR, so the second
+    /// material takes over at grazing angles.
+    Fresnel(f64),
+}
+
+pub struct Mix<A, B> {
+    a: A,
+    b: B,
+    weight: MixWeight,
+}
+
+impl<A: Material, B: Material> Mix<A, B> {
+    pub fn new(a: A, b: B, weight: f64) -> Self {
+        Mix::with_weight(a, b, MixWeight::Constant(weight))
+    }
+    pub fn textured(a: A, b: B, weight: Arc<dyn Texture + Send + Sync>) -> Self {
+        Mix::with_weight(a, b, MixWeight::Textured(weight))
+    }
+    /// Glossy-over-diffuse style blend, `b` is usually the specular layer.
+    pub fn fresnel(a: A, b: B, ior: f64) -> Self {
+        Mix::with_weight(a, b, MixWeight::Fresnel(ior))
+    }
+    pub fn with_weight(a: A, b: B, weight: MixWeight) -> Self {
+        Mix { a, b, weight }
+    }
+
+    fn weight(&self, r_in: &Ray, rec: &HitRecord) -> f64 {
+        let w = match &self.weight {
+            MixWeight::Constant(w) => *w,
+            MixWeight::Textured(texture) => texture.value(rec.u, rec.v, &rec.p).x,
+            MixWeight::Fresnel(ior) => fresnel_weight(r_in, rec, *ior),
+        };
+        clamp(w, 0.0, 1.0)
+    }
+}
+
+/// Fresnel reflectance of a dielectric boundary for the incoming ray, the
+/// IOR is inverted when hitting the back face.
+pub(crate) fn fresnel_weight(r_in: &Ray, rec: &HitRecord, ior: f64) -> f64 {
+    let cos = (-r_in.direction().unit()).dot(&rec.normal);
+    let eta = if rec.front_face { ior } else { 1.0 / ior };
+    fresnel_dielectric(cos, eta)
+}
+
+impl<A: Material, B: Material> Material for Mix<A, B> {
+    /// Picking one material with the blend probability keeps the estimate
+    /// unbiased without scaling the attenuation.
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        if random::<f64>() < self.weight(r_in, rec) {
+            self.b.scatter(r_in, rec, attenuation, scattered)
+        } else {
+            self.a.scatter(r_in, rec, attenuation, scattered)
+        }
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.eval(r_in, rec, wi) + w * self.b.eval(r_in, rec, wi)
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.pdf(r_in, rec, wi) + w * self.b.pdf(r_in, rec, wi)
+    }
+    /// Only one of the two can define the enclosed medium.
+    fn medium(&self) -> Option<&Medium> {
+        self.a.medium().or_else(|| self.b.medium())
+    }
+    fn emitted(&self, r_in: &Ray, rec: &HitRecord) -> Color {
+        let w = self.weight(r_in, rec);
+        (1.0 - w) * self.a.emitted(r_in, rec) + w * self.b.emitted(r_in, rec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn blends_evaluation_by_weight() {
+        let dark = Lambertian::new((0.2, 0.2, 0.2).into());
+        let bright = Lambertian::new((0.8, 0.8, 0.8).into());
+        let mix = Mix::new(dark, bright, 0.25);
+        let r_in = Ray::new((0, 1, 0).into(), (0, -1, 0).into());
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        let wi: Point3 = (0, 1, 0).into();
+        let f = mix.eval(&r_in, &rec, &wi);
+        assert!((f.x - 0.35 / std::f64::consts::PI).abs() < 1e-9);
+        assert!((mix.pdf(&r_in, &rec, &wi) - 1.0 / std::f64::consts::PI).abs() < 1e-9);
+
+        // Head-on the Fresnel blend barely picks the coat, ((1.5 - 1) / (1.5 + 1))^2 = 0.04.
+        let coat = Mix::fresnel(
+            Lambertian::new((0.5, 0.5, 0.5).into()),
+            Metal::new((1, 1, 1).into(), 0.0),
+            1.5,
+        );
+        assert!((coat.weight(&r_in, &rec) - 0.04).abs() < 1e-9);
+
+        // The mirror's samples are flagged, so they aren't weighted by the
+        // diffuse density `pdf` reports.
+        let mirror = Mix::new(
+            Lambertian::new((0.5, 0.5, 0.5).into()),
+            Metal::new((1, 1, 1).into(), 0.0),
+            0.5,
+        );
+        let mut flagged = 0;
+        for _ in 0..64 {
+            let mut rec = rec.clone();
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            assert!(mirror.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered));
+            let reflected = (scattered.direction().unit() - wi).len() < 1e-9;
+            assert_eq!(rec.specular, reflected);
+            flagged += rec.specular as usize;
+        }
+        assert!(flagged > 0 && flagged < 64);
+    }
+}
@@ -0,0 +1,111 @@
+//! Wavefront `.mtl` material libraries mapped onto `Principled`.
+//!
+//! Classic Phong parameters (`Kd`, `Ks`, `Ns`, `Ni`, `d`) are converted
+//! approximately, of the `illum` models only the glass ones (4, 6, 7 and 9)
+//! matter and make the material transmissive. The PBR extension (`Pr`,
+//! `Pm`, `Ps`, `Pc`, `Pcr`) is taken as is.
+use super::*;
+use std::collections::HashMap;
+use std::io;
+
+pub fn load_mtl(path: impl AsRef<std::path::Path>) -> io::Result<HashMap<String, Principled>> {
+    parse_mtl(&std::fs::read_to_string(path)?)
+}
+
+pub fn parse_mtl(src: &str) -> io::Result<HashMap<String, Principled>> {
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Principled)> = None;
+    for (n, line) in src.lines().enumerate() {
+        let invalid = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {}", n + 1, what),
+            )
+        };
+        let mut words = line.split_whitespace();
+        let keyword = match words.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+        let args = words
+            .map(|w| w.parse::<f64>())
+            .collect::<Result<Vec<_>, _>>();
+        if keyword == "newmtl" {
+            let name = line.trim_start()["newmtl".len()..].trim();
+            if name.is_empty() {
+                return Err(invalid("material without a name"));
+            }
+            if let Some((name, m)) = current.take() {
+                materials.insert(name, m);
+            }
+            current = Some((name.to_string(), Principled::default()));
+            continue;
+        }
+        let m = match current.as_mut() {
+            Some((_, m)) => m,
+            None => return Err(invalid("statement before `newmtl`")),
+        };
+        // Texture maps and unknown statements are skipped.
+        let args = match args {
+            Ok(args) if !args.is_empty() => args,
+            _ => continue,
+        };
+        let color = || -> Color {
+            match args.as_slice() {
+                [r, g, b, ..] => Color::new(*r, *g, *b),
+                [v, ..] => Color::new(*v, *v, *v),
+                [] => unreachable!(),
+            }
+        };
+        match keyword {
+            "Kd" => m.base_color = color(),
+            "Ks" => m.specular = clamp(color().luminance() / 0.08 * 0.04, 0.0, 1.0),
+            // Blinn-Phong exponent to GGX alpha, roughness = sqrt(alpha).
+            "Ns" => m.roughness = (2.0 / (args[0] + 2.0)).sqrt().sqrt(),
+            "Ni" => m.ior = args[0],
+            "d" => m.transmission = 1.0 - clamp(args[0], 0.0, 1.0),
+            "Tr" => m.transmission = clamp(args[0], 0.0, 1.0),
+            "illum" if [4.0, 6.0, 7.0, 9.0].contains(&args[0]) => m.transmission = 1.0,
+            "Pr" => m.roughness = args[0],
+            "Pm" => m.metallic = args[0],
+            "Ps" => m.sheen = args[0],
+            "Pc" => m.clearcoat = args[0],
+            "Pcr" => m.clearcoat_gloss = 1.0 - args[0],
+            _ => {}
+        }
+    }
+    if let Some((name, m)) = current {
+        materials.insert(name, m);
+    }
+    Ok(materials)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_phong_and_pbr_statements() {
+        let src = "# comment\n\
+                   newmtl red paint\n\
+                   Kd 0.8 0.1 0.1\n\
+                   Pr 0.25\n\
+                   Pc 1\n\
+                   map_Kd paint.ppm\n\
+                   newmtl glass\n\
+                   Ni 1.45\n\
+                   d 0.0\n\
+                   newmtl window\n\
+                   illum 7\n";
+        let materials = parse_mtl(src).unwrap();
+        let paint = &materials["red paint"];
+        assert_eq!(paint.base_color.x, 0.8);
+        assert_eq!(paint.roughness, 0.25);
+        assert_eq!(paint.clearcoat, 1.0);
+        let glass = &materials["glass"];
+        assert_eq!(glass.ior, 1.45);
+        assert_eq!(glass.transmission, 1.0);
+        assert_eq!(materials["window"].transmission, 1.0);
+        assert!(parse_mtl("Kd 1 1 1").is_err());
+    }
+}
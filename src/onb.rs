@@ -0,0 +1,42 @@
+use super::*;
+
+/// Orthonormal basis whose `w` axis is aligned with a given normal.
+pub struct Onb {
+    pub u: Point3,
+    pub v: Point3,
+    pub w: Point3,
+}
+
+impl Onb {
+    pub fn build_from_w(n: &Point3) -> Self {
+        let w = n.unit();
+        let a: Point3 = if w.x.abs() > 0.9 {
+            (0, 1, 0).into()
+        } else {
+            (1, 0, 0).into()
+        };
+        let v = w.cross(&a).unit();
+        let u = w.cross(&v);
+        Onb { u, v, w }
+    }
+    /// Basis with `u` along `tangent` projected onto the plane of `n`.
+    /// Falls back to an arbitrary tangent if `tangent` is parallel to `n`.
+    pub fn from_normal_tangent(n: &Point3, tangent: &Point3) -> Self {
+        let w = n.unit();
+        let t = *tangent - tangent.dot(&w) * w;
+        if t.len_squared() < 1e-12 {
+            return Onb::build_from_w(n);
+        }
+        let u = t.unit();
+        let v = w.cross(&u);
+        Onb { u, v, w }
+    }
+    /// Local -> world.
+    pub fn local(&self, a: &Point3) -> Point3 {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+    /// World -> local.
+    pub fn to_local(&self, a: &Point3) -> Point3 {
+        Point3::new(a.dot(&self.u), a.dot(&self.v), a.dot(&self.w))
+    }
+}
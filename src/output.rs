@@ -0,0 +1,213 @@
+//! Writing rendered images, in a format chosen by the file extension.
+//!
+//! Pixels are display encoded values in `[0, 1]`, starting at the top left
+//! corner; writers only quantize them.
+use super::*;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub trait ImageWriter {
+    fn write(
+        &self,
+        out: &mut dyn Write,
+        width: usize,
+        height: usize,
+        pixels: &[Color],
+    ) -> io::Result<()>;
+}
+
+/// How many bits each channel is stored with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BitDepth {
+    Eight,
+    Sixteen,
+}
+
+impl BitDepth {
+    fn max(self) -> u32 {
+        match self {
+            BitDepth::Eight => 255,
+            BitDepth::Sixteen => 65535,
+        }
+    }
+    fn quantize(self, value: f64) -> u16 {
+        match self {
+            // Equal width buckets, as the renderer always wrote them.
+            BitDepth::Eight => (clamp(value, 0.0, 0.999) * 256.0) as u16,
+            BitDepth::Sixteen => (clamp(value, 0.0, 1.0) * 65535.0).round() as u16,
+        }
+    }
+    fn samples(self, pixels: &[Color]) -> impl Iterator<Item = u16> + '_ {
+        pixels
+            .iter()
+            .flat_map(move |p| [p.x, p.y, p.z].map(|c| self.quantize(c)))
+    }
+}
+
+pub struct Png {
+    pub depth: BitDepth,
+}
+
+impl ImageWriter for Png {
+    fn write(
+        &self,
+        out: &mut dyn Write,
+        width: usize,
+        height: usize,
+        pixels: &[Color],
+    ) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(out, width as u32, height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        let data: Vec<u8> = match self.depth {
+            BitDepth::Eight => {
+                encoder.set_depth(png::BitDepth::Eight);
+                self.depth.samples(pixels).map(|s| s as u8).collect()
+            }
+            BitDepth::Sixteen => {
+                encoder.set_depth(png::BitDepth::Sixteen);
+                self.depth
+                    .samples(pixels)
+                    .flat_map(u16::to_be_bytes)
+                    .collect()
+            }
+        };
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&data).map_err(io::Error::other)?;
+        writer.finish().map_err(io::Error::other)
+    }
+}
+
+/// Netpbm color image, binary (`P6`) or plain text (`P3`).
+pub struct Ppm {
+    pub depth: BitDepth,
+    pub ascii: bool,
+}
+
+impl ImageWriter for Ppm {
+    fn write(
+        &self,
+        out: &mut dyn Write,
+        width: usize,
+        height: usize,
+        pixels: &[Color],
+    ) -> io::Result<()> {
+        let magic = if self.ascii { "P3" } else { "P6" };
+        write!(
+            out,
+            "{}\n{} {}\n{}\n",
+            magic,
+            width,
+            height,
+            self.depth.max()
+        )?;
+        if self.ascii {
+            for p in pixels {
+                let [r, g, b] = [p.x, p.y, p.z].map(|c| self.depth.quantize(c));
+                writeln!(out, "{} {} {}", r, g, b)?;
+            }
+            return Ok(());
+        }
+        let data: Vec<u8> = match self.depth {
+            BitDepth::Eight => self.depth.samples(pixels).map(|s| s as u8).collect(),
+            BitDepth::Sixteen => self
+                .depth
+                .samples(pixels)
+                .flat_map(u16::to_be_bytes)
+                .collect(),
+        };
+        out.write_all(&data)
+    }
+}
+
+/// The writer for `path`'s extension, `.png` or `.ppm`. `ascii` picks plain
+/// text `P3` over binary `P6` for PPM.
+pub fn writer_for(path: &Path, depth: BitDepth, ascii: bool) -> io::Result<Box<dyn ImageWriter>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") => Ok(Box::new(Png { depth })),
+        Some("ppm") => Ok(Box::new(Ppm { depth, ascii })),
+        _ => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "can't tell the image format of {}, use .png or .ppm",
+                path.display()
+            ),
+        )),
+    }
+}
+
+/// Writes the image to a new file at `path`.
+pub fn save(
+    writer: &dyn ImageWriter,
+    path: &Path,
+    width: usize,
+    height: usize,
+    pixels: &[Color],
+) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writer.write(&mut file, width, height, pixels)?;
+    file.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pixels() -> Vec<Color> {
+        vec![
+            (1, 0, 0).into(),
+            (0.5, 0.25, 0.0).into(),
+            (0, 0, 1).into(),
+            (2.0, -1.0, 0.999).into(),
+        ]
+    }
+
+    #[test]
+    fn writes_png_and_ppm() {
+        let mut ascii = Vec::new();
+        let writer = Ppm {
+            depth: BitDepth::Eight,
+            ascii: true,
+        };
+        writer.write(&mut ascii, 2, 2, &pixels()).unwrap();
+        assert_eq!(
+            String::from_utf8(ascii).unwrap(),
+            "P3\n2 2\n255\n255 0 0\n128 64 0\n0 0 255\n255 0 255\n"
+        );
+
+        let mut binary = Vec::new();
+        let writer = Ppm {
+            depth: BitDepth::Sixteen,
+            ascii: false,
+        };
+        writer.write(&mut binary, 2, 2, &pixels()).unwrap();
+        assert!(binary.starts_with(b"P6\n2 2\n65535\n"));
+        assert_eq!(binary.len(), b"P6\n2 2\n65535\n".len() + 2 * 2 * 3 * 2);
+
+        for depth in [BitDepth::Eight, BitDepth::Sixteen] {
+            let mut encoded = Vec::new();
+            Png { depth }.write(&mut encoded, 2, 2, &pixels()).unwrap();
+            let mut reader = png::Decoder::new(&encoded[..]).read_info().unwrap();
+            let mut decoded = vec![0; reader.output_buffer_size()];
+            let info = reader.next_frame(&mut decoded).unwrap();
+            assert_eq!((info.width, info.height), (2, 2));
+            let expected: Vec<u16> = depth.samples(&pixels()).collect();
+            let samples: Vec<u16> = match depth {
+                BitDepth::Eight => decoded.iter().map(|b| *b as u16).collect(),
+                BitDepth::Sixteen => decoded
+                    .chunks(2)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                    .collect(),
+            };
+            assert_eq!(samples, expected);
+        }
+
+        assert!(writer_for(Path::new("render.PNG"), BitDepth::Eight, false).is_ok());
+        assert!(writer_for(Path::new("render.jpg"), BitDepth::Eight, false).is_err());
+        assert!(writer_for(Path::new("render"), BitDepth::Eight, false).is_err());
+    }
+}
@@ -0,0 +1,217 @@
+//! Cameras covering the whole sphere or a wide part of it from a single
+//! point, for 360° images, fisheye shots and reflection probes.
+use super::*;
+use crate::camera::{Camera, CameraSample, Degrees};
+use std::f64::consts::PI;
+
+/// Orientation shared by the panoramic cameras, looking along `-w`.
+pub(crate) struct Frame {
+    pub(crate) origin: Point3,
+    u: Point3,
+    v: Point3,
+    w: Point3,
+}
+
+impl Frame {
+    pub(crate) fn new(lookfrom: Point3, lookat: Point3, vup: Point3) -> Self {
+        let w = (lookfrom - lookat).unit();
+        let u = vup.cross(&w).unit();
+        let v = w.cross(&u);
+        Frame {
+            origin: lookfrom,
+            u,
+            v,
+            w,
+        }
+    }
+    /// World direction of `local`, given with x right, y up and z forward.
+    pub(crate) fn to_world(&self, local: &Point3) -> Point3 {
+        local.x * self.u + local.y * self.v - local.z * self.w
+    }
+    fn ray(&self, local: Point3, time: f64) -> Ray {
+        Ray::new(self.origin, self.to_world(&local)).with_time(time)
+    }
+}
+
+/// Latitude-longitude panorama with the view direction at the center of a
+/// 2:1 image, longitude increasing to the right.
+pub struct EquirectangularCamera {
+    frame: Frame,
+}
+
+impl EquirectangularCamera {
+    pub fn new(lookfrom: Point3, lookat: Point3, vup: Point3) -> Self {
+        EquirectangularCamera {
+            frame: Frame::new(lookfrom, lookat, vup),
+        }
+    }
+}
+
+/// Unit direction in a frame looking along +z with y up, for the film
+/// position `(s, t)` of a latitude-longitude image.
+pub(crate) fn equirectangular((s, t): (f64, f64)) -> Point3 {
+    let longitude = (s - 0.5) * 2.0 * PI;
+    let latitude = (t - 0.5) * PI;
+    Point3::new(
+        latitude.cos() * longitude.sin(),
+        latitude.sin(),
+        latitude.cos() * longitude.cos(),
+    )
+}
+
+impl Camera for EquirectangularCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        Some(self.frame.ray(equirectangular(sample.film), sample.time))
+    }
+}
+
+pub enum FisheyeProjection {
+    /// Image radius proportional to the angle off the axis.
+    Equidistant,
+    /// Equal areas on the image cover equal solid angles.
+    Equisolid,
+}
+
+/// Fisheye whose image circle spans the height of the film, the width
+/// being `aspect_ratio` times larger. Outside the circle it sees nothing.
+pub struct FisheyeCamera {
+    frame: Frame,
+    projection: FisheyeProjection,
+    /// Half the field of view, in radians.
+    theta_max: f64,
+    aspect_ratio: f64,
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Point3,
+        fov: Degrees,
+        aspect_ratio: f64,
+        projection: FisheyeProjection,
+    ) -> Self {
+        let fov: f64 = fov.to_radians().into();
+        FisheyeCamera {
+            frame: Frame::new(lookfrom, lookat, vup),
+            projection,
+            theta_max: clamp(fov, 0.0, 2.0 * PI) / 2.0,
+            aspect_ratio,
+        }
+    }
+}
+
+impl Camera for FisheyeCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let x = (2.0 * sample.film.0 - 1.0) * self.aspect_ratio;
+        let y = 2.0 * sample.film.1 - 1.0;
+        let r = (x * x + y * y).sqrt();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.projection {
+            FisheyeProjection::Equidistant => r * self.theta_max,
+            FisheyeProjection::Equisolid => {
+                2.0 * clamp(r * (self.theta_max / 2.0).sin(), -1.0, 1.0).asin()
+            }
+        };
+        let (cos_phi, sin_phi) = if r > 0.0 { (x / r, y / r) } else { (1.0, 0.0) };
+        let local = Point3::new(theta.sin() * cos_phi, theta.sin() * sin_phi, theta.cos());
+        Some(self.frame.ray(local, sample.time))
+    }
+}
+
+/// The six faces of a cube map side by side in a 6:1 image, in the order
+/// +x, -x, +y, -y, +z, -z with the OpenGL orientation of each face, as
+/// reflection probes expect. The faces are aligned with the world axes.
+pub struct CubemapCamera {
+    origin: Point3,
+}
+
+impl CubemapCamera {
+    pub fn new(origin: Point3) -> Self {
+        CubemapCamera { origin }
+    }
+}
+
+impl Camera for CubemapCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let (s, t) = sample.film;
+        let scaled = clamp(s, 0.0, 1.0) * 6.0;
+        let face = (scaled as usize).min(5);
+        // Face coordinates in [-1, 1], b pointing up in the image.
+        let a = 2.0 * (scaled - face as f64) - 1.0;
+        let b = 2.0 * t - 1.0;
+        let direction = match face {
+            0 => Point3::new(1.0, b, -a),
+            1 => Point3::new(-1.0, b, a),
+            2 => Point3::new(a, 1.0, -b),
+            3 => Point3::new(a, -1.0, b),
+            4 => Point3::new(a, b, 1.0),
+            _ => Point3::new(-a, b, -1.0),
+        };
+        Some(Ray::new(self.origin, direction).with_time(sample.time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn direction(camera: &dyn Camera, film: (f64, f64)) -> Option<Point3> {
+        let sample = CameraSample {
+            film,
+            ..Default::default()
+        };
+        camera
+            .generate_ray(&sample)
+            .map(|ray| ray.direction().unit())
+    }
+
+    #[test]
+    fn panoramic_directions() {
+        let (from, at, up) = (Point3::zeroed(), (0, 0, -1).into(), (0, 1, 0).into());
+        let equirect = EquirectangularCamera::new(from, at, up);
+        let forward = direction(&equirect, (0.5, 0.5)).unwrap();
+        assert!((forward.z + 1.0).abs() < 1e-12);
+        let right = direction(&equirect, (0.75, 0.5)).unwrap();
+        assert!((right.x - 1.0).abs() < 1e-12);
+        assert!((direction(&equirect, (0.3, 1.0)).unwrap().y - 1.0).abs() < 1e-12);
+
+        for projection in [FisheyeProjection::Equidistant, FisheyeProjection::Equisolid] {
+            let fisheye = FisheyeCamera::new(from, at, up, Degrees::new(180.0), 1.0, projection);
+            assert!((direction(&fisheye, (0.5, 0.5)).unwrap().z + 1.0).abs() < 1e-12);
+            // The rim of a 180° fisheye looks sideways.
+            assert!((direction(&fisheye, (1.0, 0.5)).unwrap().x - 1.0).abs() < 1e-12);
+            assert!(direction(&fisheye, (1.0, 1.0)).is_none());
+        }
+        // Halfway out, equisolid bends less towards the axis than equidistant's 45°.
+        let equisolid = FisheyeCamera::new(
+            from,
+            at,
+            up,
+            Degrees::new(180.0),
+            1.0,
+            FisheyeProjection::Equisolid,
+        );
+        let halfway = direction(&equisolid, (0.75, 0.5)).unwrap();
+        assert!((halfway.x - (2.0 * (0.5f64 * 0.5f64.sqrt()).asin()).sin()).abs() < 1e-12);
+
+        let cubemap = CubemapCamera::new(Point3::zeroed());
+        let centers = [
+            (1, 0, 0),
+            (-1, 0, 0),
+            (0, 1, 0),
+            (0, -1, 0),
+            (0, 0, 1),
+            (0, 0, -1),
+        ];
+        for (face, center) in centers.iter().enumerate() {
+            let d = direction(&cubemap, ((face as f64 + 0.5) / 6.0, 0.5)).unwrap();
+            let expected: Point3 = (*center).into();
+            assert!((d - expected).len() < 1e-12);
+        }
+        // The top of the +z face is up.
+        assert!(direction(&cubemap, (4.5 / 6.0, 0.9)).unwrap().y > 0.0);
+    }
+}
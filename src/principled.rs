@@ -0,0 +1,363 @@
+//! Disney "principled" BSDF (Burley 2012/2015), the uber-material used by
+//! Blender and glTF.
+use super::*;
+use std::f64::consts::PI;
+
+pub struct Principled {
+    pub base_color: Color,
+    pub metallic: f64,
+    pub roughness: f64,
+    /// Dielectric specular amount, 0.5 corresponds to an IOR of 1.5.
+    pub specular: f64,
+    pub specular_tint: f64,
+    pub sheen: f64,
+    pub sheen_tint: f64,
+    pub clearcoat: f64,
+    pub clearcoat_gloss: f64,
+    pub transmission: f64,
+    pub ior: f64,
+    /// Blends the diffuse lobe towards the Hanrahan-Krueger approximation.
+    pub subsurface: f64,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            base_color: (0.8, 0.8, 0.8).into(),
+            metallic: 0.0,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.0,
+            sheen: 0.0,
+            sheen_tint: 0.5,
+            clearcoat: 0.0,
+            clearcoat_gloss: 1.0,
+            transmission: 0.0,
+            ior: 1.5,
+            subsurface: 0.0,
+        }
+    }
+}
+
+fn schlick_weight(cosine: f64) -> f64 {
+    let m = clamp(1.0 - cosine, 0.0, 1.0);
+    (m * m) * (m * m) * m
+}
+
+fn lerp(a: Color, b: Color, t: f64) -> Color {
+    (1.0 - t) * a + t * b
+}
+
+/// Generalized Trowbridge-Reitz with `gamma = 1`, used by the clearcoat lobe.
+fn gtr1(cos_h: f64, alpha: f64) -> f64 {
+    let a2 = alpha * alpha;
+    (a2 - 1.0) / (PI * a2.ln() * (1.0 + (a2 - 1.0) * cos_h * cos_h))
+}
+
+fn sample_gtr1(alpha: f64) -> Point3 {
+    let a2 = alpha * alpha;
+    let cos2 = (1.0 - a2.powf(1.0 - random::<f64>())) / (1.0 - a2);
+    let cos_h = cos2.max(0.0).sqrt();
+    let sin_h = (1.0 - cos2).max(0.0).sqrt();
+    let phi = 2.0 * PI * random::<f64>();
+    Point3::new(sin_h * phi.cos(), sin_h * phi.sin(), cos_h)
+}
+
+/// Probabilities of picking each reflection lobe.
+struct LobeWeights {
+    diffuse: f64,
+    specular: f64,
+    clearcoat: f64,
+}
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self {
+        Principled {
+            base_color,
+            ..Default::default()
+        }
+    }
+
+    fn tint(&self) -> Color {
+        let lum = self.base_color.luminance();
+        if lum > 0.0 {
+            self.base_color / lum
+        } else {
+            (1, 1, 1).into()
+        }
+    }
+    fn diffuse_weight(&self) -> f64 {
+        (1.0 - self.metallic) * (1.0 - self.transmission)
+    }
+    fn transmission_weight(&self) -> f64 {
+        (1.0 - self.metallic) * self.transmission
+    }
+    /// The transmission lobe, tinted by `base_color` where it's used.
+    fn glass(&self) -> RoughDielectric {
+        RoughDielectric::new(self.ior, self.roughness)
+    }
+    fn specular_color(&self) -> Color {
+        let white: Color = (1, 1, 1).into();
+        let dielectric = 0.08 * self.specular * lerp(white, self.tint(), self.specular_tint);
+        lerp(dielectric, self.base_color, self.metallic)
+    }
+    fn clearcoat_alpha(&self) -> f64 {
+        0.1 * (1.0 - self.clearcoat_gloss) + 0.001 * self.clearcoat_gloss
+    }
+    fn lobe_weights(&self) -> LobeWeights {
+        let diffuse = self.diffuse_weight() * self.base_color.luminance();
+        let specular = self.specular_color().luminance().max(0.04);
+        let clearcoat = 0.25 * self.clearcoat;
+        let total = diffuse + specular + clearcoat;
+        LobeWeights {
+            diffuse: diffuse / total,
+            specular: specular / total,
+            clearcoat: clearcoat / total,
+        }
+    }
+
+    /// Reflection part of the BSDF times cosine, both directions in the local frame.
+    fn eval_local(&self, wo: &Point3, wi: &Point3) -> Color {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return Color::zeroed();
+        }
+        let h = (*wo + *wi).unit();
+        let cos_d = wi.dot(&h);
+        let fl = schlick_weight(wi.z);
+        let fv = schlick_weight(wo.z);
+        let fh = schlick_weight(cos_d);
+
+        let fd90 = 0.5 + 2.0 * cos_d * cos_d * self.roughness;
+        let fd = (1.0 + (fd90 - 1.0) * fl) * (1.0 + (fd90 - 1.0) * fv);
+        let fss90 = cos_d * cos_d * self.roughness;
+        let fss = (1.0 + (fss90 - 1.0) * fl) * (1.0 + (fss90 - 1.0) * fv);
+        let ss = 1.25 * (fss * (1.0 / (wi.z + wo.z) - 0.5) + 0.5);
+        let diffuse = ((fd + (ss - fd) * self.subsurface) / PI) * self.base_color;
+
+        let white: Color = (1, 1, 1).into();
+        let sheen = (fh * self.sheen) * lerp(white, self.tint(), self.sheen_tint);
+
+        let ggx = Ggx::new(self.roughness);
+        let f = lerp(self.specular_color(), white, fh);
+        let specular = (ggx.d(&h) * ggx.g(wo, wi) / (4.0 * wo.z * wi.z)) * f;
+
+        let cc_alpha = self.clearcoat_alpha();
+        let cc_ggx = Ggx::new(0.5);
+        let cc =
+            0.25 * self.clearcoat * gtr1(h.z, cc_alpha) * cc_ggx.g(wo, wi) * (0.04 + 0.96 * fh)
+                / (4.0 * wo.z * wi.z);
+
+        // The transmission lobe reflects by its own Fresnel term, the
+        // reflection lobes only cover what it leaves (Burley 2015).
+        let reflection = 1.0 - self.transmission_weight();
+        wi.z * (self.diffuse_weight() * (diffuse + sheen)
+            + reflection * (specular + (cc, cc, cc).into()))
+    }
+    /// Density of the reflection lobes, not including the transmission probability.
+    fn pdf_local(&self, wo: &Point3, wi: &Point3) -> f64 {
+        if wo.z <= 0.0 || wi.z <= 0.0 {
+            return 0.0;
+        }
+        let w = self.lobe_weights();
+        let h = (*wo + *wi).unit();
+        let ggx = Ggx::new(self.roughness);
+        let specular = ggx.pdf_vndf(wo, &h) / (4.0 * wo.dot(&h));
+        let clearcoat = gtr1(h.z, self.clearcoat_alpha()) * h.z / (4.0 * wo.dot(&h));
+        w.diffuse * wi.z / PI + w.specular * specular + w.clearcoat * clearcoat
+    }
+}
+
+impl Material for Principled {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        let transmission = self.transmission_weight();
+        if (!rec.front_face && transmission > 0.0) || random::<f64>() < transmission {
+            // Rays inside a transmissive object can only leave through the
+            // transmission lobe.
+            let glass = self.glass();
+            if !glass.scatter(r_in, rec, attenuation, scattered) {
+                return false;
+            }
+            *attenuation *= self.base_color;
+            return true;
+        }
+
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        if wo.z <= 0.0 {
+            return false;
+        }
+        let w = self.lobe_weights();
+        let xi = random::<f64>();
+        let wi = if xi < w.diffuse {
+            frame.to_local(&(rec.normal + Vec3::random_unit()).unit())
+        } else if xi < w.diffuse + w.specular {
+            let h = Ggx::new(self.roughness).sample_vndf(&wo);
+            (-wo).reflect(&h)
+        } else {
+            let h = sample_gtr1(self.clearcoat_alpha());
+            (-wo).reflect(&h)
+        };
+        let pdf = self.pdf_local(&wo, &wi) * (1.0 - transmission);
+        if wi.z <= 0.0 || pdf <= 0.0 {
+            return false;
+        }
+        *scattered = Ray::new(rec.p, frame.local(&wi));
+        *attenuation = self.eval_local(&wo, &wi) / pdf;
+        true
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        let transmission = self.transmission_weight();
+        let glass = if transmission > 0.0 {
+            self.base_color * self.glass().eval(r_in, rec, wi)
+        } else {
+            Color::zeroed()
+        };
+        if !rec.front_face && transmission > 0.0 {
+            return glass;
+        }
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        self.eval_local(&wo, &frame.to_local(&wi.unit())) + transmission * glass
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        let transmission = self.transmission_weight();
+        let glass = if transmission > 0.0 {
+            self.glass().pdf(r_in, rec, wi)
+        } else {
+            0.0
+        };
+        if !rec.front_face && transmission > 0.0 {
+            return glass;
+        }
+        let frame = Onb::build_from_w(&rec.normal);
+        let wo = frame.to_local(&-r_in.direction().unit());
+        self.pdf_local(&wo, &frame.to_local(&wi.unit())) * (1.0 - transmission)
+            + transmission * glass
+    }
+}
+
+/// Metallic-roughness material as described by glTF 2.0, including the
+/// `KHR_materials_transmission`, `_ior`, `_clearcoat`, `_sheen` and
+/// `_specular` extensions. Texture slots are not supported.
+pub struct GltfMaterial {
+    pub base_color_factor: [f64; 4],
+    pub metallic_factor: f64,
+    pub roughness_factor: f64,
+    pub transmission_factor: f64,
+    pub ior: f64,
+    pub clearcoat_factor: f64,
+    pub clearcoat_roughness_factor: f64,
+    pub sheen_color_factor: [f64; 3],
+    pub specular_factor: f64,
+}
+
+impl Default for GltfMaterial {
+    /// Defaults from the glTF specification.
+    fn default() -> Self {
+        GltfMaterial {
+            base_color_factor: [1.0; 4],
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            sheen_color_factor: [0.0; 3],
+            specular_factor: 1.0,
+        }
+    }
+}
+
+impl From<&GltfMaterial> for Principled {
+    fn from(m: &GltfMaterial) -> Self {
+        let [r, g, b, _] = m.base_color_factor;
+        let [sr, sg, sb] = m.sheen_color_factor;
+        let f0 = ((m.ior - 1.0) / (m.ior + 1.0)).powi(2);
+        let sheen = sr.max(sg).max(sb);
+        Principled {
+            base_color: Color::new(r, g, b),
+            metallic: m.metallic_factor,
+            roughness: m.roughness_factor,
+            specular: m.specular_factor * f0 / 0.08,
+            specular_tint: 0.0,
+            sheen,
+            sheen_tint: if sheen > 0.0 { 1.0 } else { 0.0 },
+            clearcoat: m.clearcoat_factor,
+            clearcoat_gloss: 1.0 - m.clearcoat_roughness_factor,
+            transmission: m.transmission_factor,
+            ior: m.ior,
+            subsurface: 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scatter_weight_matches_eval_over_pdf() {
+        let material = Principled {
+            base_color: (0.8, 0.3, 0.2).into(),
+            metallic: 0.3,
+            roughness: 0.4,
+            sheen: 0.5,
+            clearcoat: 0.7,
+            ..Default::default()
+        };
+        let r_in = Ray::new((0, 1, 1).into(), (0, -1, -1).into());
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        for _ in 0..100 {
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            if material.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered) {
+                let wi = scattered.direction();
+                let expected = material.eval(&r_in, &rec, wi) / material.pdf(&r_in, &rec, wi);
+                assert!((attenuation - expected).len() < 1e-6);
+            }
+        }
+
+        // Opaque materials reflect from inside too instead of turning into glass.
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, -1, 0).into());
+        for _ in 0..100 {
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            if material.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered) {
+                assert!(scattered.direction().dot(&rec.normal) > 0.0 && !rec.specular);
+            }
+        }
+
+        // White furnace at a grazing angle: the reflection lobes and the
+        // glass together must not return more than comes in. `eval` covers
+        // both, the glass sampler reaches every direction they do.
+        let glass = Principled {
+            base_color: (1, 1, 1).into(),
+            roughness: 0.3,
+            transmission: 1.0,
+            ..Default::default()
+        };
+        let r_in = Ray::new((0.0, 0.2, 1.0).into(), (0.0, -0.2, -1.0).into());
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        let n = 20000;
+        let mut albedo = 0.0;
+        for _ in 0..n {
+            let mut attenuation = Color::zeroed();
+            let mut scattered = Ray::default();
+            if glass.scatter(&r_in, &mut rec, &mut attenuation, &mut scattered) {
+                let wi = scattered.direction();
+                let pdf = glass.pdf(&r_in, &rec, wi);
+                albedo += glass.eval(&r_in, &rec, wi).luminance() / pdf / n as f64;
+            }
+        }
+        assert!(albedo <= 1.0 + 1e-9, "albedo {}", albedo);
+    }
+}
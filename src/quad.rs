@@ -0,0 +1,59 @@
+use super::*;
+use std::sync::Arc;
+
+/// Parallelogram spanned by `u` and `v` from the corner `q`.
+pub struct Quad {
+    q: Point3,
+    u: Point3,
+    v: Point3,
+    w: Point3,
+    normal: Point3,
+    d: f64,
+    material: Arc<dyn Material + Send + Sync>,
+}
+
+impl Quad {
+    pub fn new(q: Point3, u: Point3, v: Point3, material: Arc<dyn Material + Send + Sync>) -> Self {
+        let n = u.cross(&v);
+        let normal = n.unit();
+        Quad {
+            q,
+            u,
+            v,
+            w: n / n.dot(&n),
+            d: normal.dot(&q),
+            normal,
+            material,
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        let denom = self.normal.dot(ray.direction());
+        if denom.abs() < 1e-8 {
+            return false;
+        }
+        let t = (self.d - self.normal.dot(ray.origin())) / denom;
+        if t <= t_min || t >= t_max {
+            return false;
+        }
+        let p = ray.at(t);
+        let planar = p - self.q;
+        let alpha = self.w.dot(&planar.cross(&self.v));
+        let beta = self.w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return false;
+        }
+        rec.t = t;
+        rec.p = p;
+        rec.u = alpha;
+        rec.v = beta;
+        rec.dpdu = self.u;
+        rec.dpdv = self.v;
+        rec.set_face_normal(ray, &self.normal);
+        rec.material = Some(Arc::clone(&self.material));
+        rec.primitive = 0;
+        true
+    }
+}
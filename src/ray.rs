@@ -3,11 +3,24 @@ use crate::Point3;
 pub struct Ray {
     origin: Point3,
     direction: Point3,
+    /// Within the shutter interval, from 0 when it opens to 1 when it closes.
+    time: f64,
 }
 
 impl Ray {
     pub fn new(origin: Point3, direction: Point3) -> Self {
-        Ray { origin, direction }
+        Ray {
+            origin,
+            direction,
+            time: 0.0,
+        }
+    }
+    pub fn with_time(mut self, time: f64) -> Self {
+        self.time = time;
+        self
+    }
+    pub fn time(&self) -> f64 {
+        self.time
     }
     pub fn origin(&self) -> &Point3 {
         &self.origin
@@ -0,0 +1,481 @@
+//! Shader node graphs composing textures and BSDFs into a single material.
+//!
+//! Color nodes are evaluated at every shading point and feed the inputs of
+//! BSDF nodes, which are instantiated per hit. Graphs can be built in code
+//! or read from a small text format, one node per line:
+//!
+//! ```text
+//! # comments start with '#'
+//! wood = texture wood.ppm
+//! grime = noise 4
+//! dirt = ramp grime 0 1,1,1 1 0.3,0.25,0.2
+//! albedo = multiply wood dirt
+//! base = diffuse albedo
+//! coat = glossy 1,1,1 0.05
+//! f = fresnel 1.5
+//! varnish = mix_shader base coat f
+//! output varnish
+//! ```
+//!
+//! Inputs are either earlier node names or literals, a number for gray or
+//! `r,g,b` for a color.
+use super::*;
+use std::collections::HashMap;
+use std::io;
+use std::sync::Arc;
+
+pub type NodeId = usize;
+
+pub enum Node {
+    Constant(Color),
+    Texture(Arc<dyn Texture + Send + Sync>),
+    /// Red channel of `input` mapped through piecewise linear color stops,
+    /// sorted by position.
+    ColorRamp {
+        input: NodeId,
+        stops: Vec<(f64, Color)>,
+    },
+    Multiply(NodeId, NodeId),
+    /// Linear blend of two colors by the red channel of `factor`.
+    Mix {
+        a: NodeId,
+        b: NodeId,
+        factor: NodeId,
+    },
+    /// Dielectric Fresnel reflectance for the incoming ray as a gray color.
+    Fresnel {
+        ior: f64,
+    },
+    Diffuse {
+        color: NodeId,
+    },
+    Glossy {
+        color: NodeId,
+        roughness: NodeId,
+    },
+    Glass {
+        ior: f64,
+        roughness: NodeId,
+    },
+    /// Picks `b` with the probability given by the red channel of `factor`, `a` otherwise.
+    MixShader {
+        a: NodeId,
+        b: NodeId,
+        factor: NodeId,
+    },
+}
+
+impl Node {
+    /// Inputs read as colors or scalars.
+    fn color_inputs(&self) -> Vec<NodeId> {
+        match self {
+            Node::Constant(_) | Node::Texture(_) | Node::Fresnel { .. } => vec![],
+            Node::ColorRamp { input, .. } => vec![*input],
+            Node::Multiply(a, b) => vec![*a, *b],
+            Node::Mix { a, b, factor } => vec![*a, *b, *factor],
+            Node::MixShader { factor, .. } => vec![*factor],
+            Node::Diffuse { color } => vec![*color],
+            Node::Glossy { color, roughness } => vec![*color, *roughness],
+            Node::Glass { roughness, .. } => vec![*roughness],
+        }
+    }
+    /// Inputs that must be BSDF nodes.
+    fn shader_inputs(&self) -> Vec<NodeId> {
+        match self {
+            Node::MixShader { a, b, .. } => vec![*a, *b],
+            _ => vec![],
+        }
+    }
+    fn is_shader(&self) -> bool {
+        matches!(
+            self,
+            Node::Diffuse { .. }
+                | Node::Glossy { .. }
+                | Node::Glass { .. }
+                | Node::MixShader { .. }
+        )
+    }
+}
+
+/// A BSDF node instantiated with its input values.
+enum Bsdf {
+    Diffuse(Lambertian),
+    Glossy(Principled),
+    Glass(RoughDielectric),
+}
+
+impl Bsdf {
+    fn material(&self) -> &dyn Material {
+        match self {
+            Bsdf::Diffuse(m) => m,
+            Bsdf::Glossy(m) => m,
+            Bsdf::Glass(m) => m,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ShaderGraph {
+    nodes: Vec<Node>,
+    /// Whether a node evaluates to the same value at every hit.
+    constant: Vec<bool>,
+    /// BSDF nodes with constant inputs, instantiated once when added.
+    compiled: Vec<Option<Bsdf>>,
+    output: Option<NodeId>,
+}
+
+impl ShaderGraph {
+    pub fn new() -> Self {
+        Default::default()
+    }
+    /// Nodes may only reference nodes added before them, BSDF nodes only
+    /// where a shader is expected.
+    pub fn add(&mut self, node: Node) -> NodeId {
+        assert!(self.accepts(&node));
+        let constant = match node {
+            Node::Constant(_) => true,
+            Node::Texture(_) | Node::Fresnel { .. } => false,
+            _ => node.color_inputs().iter().all(|i| self.constant[*i]),
+        };
+        self.nodes.push(node);
+        self.constant.push(constant);
+        let id = self.nodes.len() - 1;
+        let compiled = if constant && self.nodes[id].is_shader() {
+            // Constant inputs don't depend on the hit.
+            let r_in = Ray::default();
+            self.build(id, &r_in, &HitRecord::default())
+        } else {
+            None
+        };
+        self.compiled.push(compiled);
+        id
+    }
+    /// Whether all inputs of `node` exist and are of the kind its slots expect.
+    fn accepts(&self, node: &Node) -> bool {
+        let is_shader = |i: &NodeId| self.nodes.get(*i).map(Node::is_shader);
+        node.color_inputs()
+            .iter()
+            .all(|i| is_shader(i) == Some(false))
+            && node
+                .shader_inputs()
+                .iter()
+                .all(|i| is_shader(i) == Some(true))
+    }
+    /// `output` must be a BSDF node.
+    pub fn set_output(&mut self, output: NodeId) {
+        assert!(self.nodes[output].is_shader());
+        self.output = Some(output);
+    }
+
+    pub fn load(path: impl AsRef<std::path::Path>) -> io::Result<Self> {
+        ShaderGraph::parse(&std::fs::read_to_string(path)?)
+    }
+    pub fn parse(src: &str) -> io::Result<Self> {
+        let mut graph = ShaderGraph::new();
+        let mut names: HashMap<&str, NodeId> = HashMap::new();
+        for (n, line) in src.lines().enumerate() {
+            let invalid = |what: String| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("line {}: {}", n + 1, what),
+                )
+            };
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let words: Vec<&str> = line.split_whitespace().collect();
+            if words[0] == "output" {
+                match words.get(1).and_then(|name| names.get(name)) {
+                    Some(id) if graph.nodes[*id].is_shader() => graph.output = Some(*id),
+                    _ => return Err(invalid("output must name a shader node".into())),
+                }
+                continue;
+            }
+            if words.len() < 3 || words[1] != "=" {
+                return Err(invalid("expected `name = node arguments...`".into()));
+            }
+            let (name, kind, args) = (words[0], words[2], &words[3..]);
+            let arg = |i: usize| {
+                args.get(i)
+                    .copied()
+                    .ok_or_else(|| invalid(format!("`{}` is missing arguments", kind)))
+            };
+            let number = |i: usize| {
+                arg(i)?
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|x| x.is_finite())
+                    .ok_or_else(|| invalid(format!("`{}` is not a number", args[i])))
+            };
+            let input = |graph: &mut ShaderGraph, i: usize| -> io::Result<NodeId> {
+                let word = arg(i)?;
+                if let Some(id) = names.get(word) {
+                    return Ok(*id);
+                }
+                let color =
+                    parse_color(word).ok_or_else(|| invalid(format!("unknown node `{}`", word)))?;
+                Ok(graph.add(Node::Constant(color)))
+            };
+            let node = match kind {
+                "constant" => {
+                    Node::Constant(parse_color(arg(0)?).ok_or_else(|| invalid("bad color".into()))?)
+                }
+                "texture" => Node::Texture(Arc::new(ImageTexture::load_ppm(arg(0)?)?)),
+                "noise" => Node::Texture(Arc::new(NoiseTexture::new(number(0)?))),
+                "ramp" => {
+                    let input = input(&mut graph, 0)?;
+                    let mut stops = Vec::new();
+                    for i in (1..args.len()).step_by(2) {
+                        let color =
+                            parse_color(arg(i + 1)?).ok_or_else(|| invalid("bad color".into()))?;
+                        stops.push((number(i)?, color));
+                    }
+                    if stops.is_empty() {
+                        return Err(invalid("ramp without stops".into()));
+                    }
+                    stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+                    Node::ColorRamp { input, stops }
+                }
+                "multiply" => Node::Multiply(input(&mut graph, 0)?, input(&mut graph, 1)?),
+                "mix" => Node::Mix {
+                    a: input(&mut graph, 0)?,
+                    b: input(&mut graph, 1)?,
+                    factor: input(&mut graph, 2)?,
+                },
+                "mix_shader" => Node::MixShader {
+                    a: input(&mut graph, 0)?,
+                    b: input(&mut graph, 1)?,
+                    factor: input(&mut graph, 2)?,
+                },
+                "fresnel" => Node::Fresnel { ior: number(0)? },
+                "diffuse" => Node::Diffuse {
+                    color: input(&mut graph, 0)?,
+                },
+                "glossy" => Node::Glossy {
+                    color: input(&mut graph, 0)?,
+                    roughness: input(&mut graph, 1)?,
+                },
+                "glass" => Node::Glass {
+                    ior: number(0)?,
+                    roughness: input(&mut graph, 1)?,
+                },
+                _ => return Err(invalid(format!("unknown node type `{}`", kind))),
+            };
+            if !graph.accepts(&node) {
+                return Err(invalid(format!(
+                    "`{}` takes shaders only where it mixes shaders",
+                    kind
+                )));
+            }
+            names.insert(name, graph.add(node));
+        }
+        if graph.output.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "shader graph has no output",
+            ));
+        }
+        Ok(graph)
+    }
+
+    fn color(&self, id: NodeId, r_in: &Ray, rec: &HitRecord) -> Color {
+        match &self.nodes[id] {
+            Node::Constant(c) => *c,
+            Node::Texture(t) => t.value(rec.u, rec.v, &rec.p),
+            Node::ColorRamp { input, stops } => {
+                let x = self.color(*input, r_in, rec).x;
+                let next = stops.iter().position(|(pos, _)| *pos > x);
+                match next {
+                    Some(0) => stops[0].1,
+                    None => stops[stops.len() - 1].1,
+                    Some(i) => {
+                        let ((p0, c0), (p1, c1)) = (stops[i - 1], stops[i]);
+                        let t = (x - p0) / (p1 - p0);
+                        (1.0 - t) * c0 + t * c1
+                    }
+                }
+            }
+            Node::Multiply(a, b) => self.color(*a, r_in, rec) * self.color(*b, r_in, rec),
+            Node::Mix { a, b, factor } => {
+                let t = self.scalar(*factor, r_in, rec);
+                (1.0 - t) * self.color(*a, r_in, rec) + t * self.color(*b, r_in, rec)
+            }
+            Node::Fresnel { ior } => {
+                let f = crate::mix::fresnel_weight(r_in, rec, *ior);
+                Color::new(f, f, f)
+            }
+            _ => unreachable!("shader nodes have no color"),
+        }
+    }
+    fn scalar(&self, id: NodeId, r_in: &Ray, rec: &HitRecord) -> f64 {
+        clamp(self.color(id, r_in, rec).x, 0.0, 1.0)
+    }
+
+    /// Instantiates a BSDF node other than `MixShader` at the hit.
+    fn build(&self, id: NodeId, r_in: &Ray, rec: &HitRecord) -> Option<Bsdf> {
+        Some(match &self.nodes[id] {
+            Node::Diffuse { color } => {
+                Bsdf::Diffuse(Lambertian::new(self.color(*color, r_in, rec)))
+            }
+            Node::Glossy { color, roughness } => Bsdf::Glossy(Principled {
+                base_color: self.color(*color, r_in, rec),
+                metallic: 1.0,
+                roughness: self.scalar(*roughness, r_in, rec),
+                ..Default::default()
+            }),
+            Node::Glass { ior, roughness } => Bsdf::Glass(RoughDielectric::new(
+                *ior,
+                self.scalar(*roughness, r_in, rec),
+            )),
+            _ => return None,
+        })
+    }
+
+    fn scatter_node(
+        &self,
+        id: NodeId,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        if let Node::MixShader { a, b, factor } = &self.nodes[id] {
+            let id = if random::<f64>() < self.scalar(*factor, r_in, rec) {
+                *b
+            } else {
+                *a
+            };
+            return self.scatter_node(id, r_in, rec, attenuation, scattered);
+        }
+        let at_hit;
+        let bsdf = match &self.compiled[id] {
+            Some(bsdf) => bsdf,
+            None => {
+                at_hit = self.build(id, r_in, rec).expect("not a BSDF node");
+                &at_hit
+            }
+        };
+        bsdf.material().scatter(r_in, rec, attenuation, scattered)
+    }
+    fn eval_node(&self, id: NodeId, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        if let Node::MixShader { a, b, factor } = &self.nodes[id] {
+            let t = self.scalar(*factor, r_in, rec);
+            return (1.0 - t) * self.eval_node(*a, r_in, rec, wi)
+                + t * self.eval_node(*b, r_in, rec, wi);
+        }
+        match &self.compiled[id] {
+            Some(bsdf) => bsdf.material().eval(r_in, rec, wi),
+            None => self
+                .build(id, r_in, rec)
+                .expect("not a BSDF node")
+                .material()
+                .eval(r_in, rec, wi),
+        }
+    }
+    fn pdf_node(&self, id: NodeId, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        if let Node::MixShader { a, b, factor } = &self.nodes[id] {
+            let t = self.scalar(*factor, r_in, rec);
+            return (1.0 - t) * self.pdf_node(*a, r_in, rec, wi)
+                + t * self.pdf_node(*b, r_in, rec, wi);
+        }
+        match &self.compiled[id] {
+            Some(bsdf) => bsdf.material().pdf(r_in, rec, wi),
+            None => self
+                .build(id, r_in, rec)
+                .expect("not a BSDF node")
+                .material()
+                .pdf(r_in, rec, wi),
+        }
+    }
+}
+
+fn parse_color(word: &str) -> Option<Color> {
+    let values = word
+        .split(',')
+        .map(|v| v.parse::<f64>().ok().filter(|v| v.is_finite()))
+        .collect::<Option<Vec<_>>>()?;
+    match values.as_slice() {
+        [v] => Some(Color::new(*v, *v, *v)),
+        [r, g, b] => Some(Color::new(*r, *g, *b)),
+        _ => None,
+    }
+}
+
+impl Material for ShaderGraph {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        match self.output {
+            Some(output) => self.scatter_node(output, r_in, rec, attenuation, scattered),
+            None => false,
+        }
+    }
+    fn eval(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> Color {
+        match self.output {
+            Some(output) => self.eval_node(output, r_in, rec, wi),
+            None => Color::zeroed(),
+        }
+    }
+    fn pdf(&self, r_in: &Ray, rec: &HitRecord, wi: &Point3) -> f64 {
+        match self.output {
+            Some(output) => self.pdf_node(output, r_in, rec, wi),
+            None => 0.0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_and_evaluates_graph() {
+        let graph = ShaderGraph::parse(
+            "# dirt over a red base\n\
+             base = constant 0.8,0.1,0.1\n\
+             ramp = ramp 0.5 0 0,0,0 1 1,1,1\n\
+             dirty = multiply base ramp\n\
+             out = diffuse dirty\n\
+             output out\n",
+        )
+        .unwrap();
+        let r_in = Ray::new((0, 1, 0).into(), (0, -1, 0).into());
+        let mut rec = HitRecord::default();
+        rec.set_face_normal(&r_in, &(0, 1, 0).into());
+        // The literal 0.5 becomes node 1, so `dirty` is node 3.
+        let c = graph.color(3, &r_in, &rec);
+        assert!((c.x - 0.4).abs() < 1e-9 && (c.y - 0.05).abs() < 1e-9);
+        let wi: Point3 = (0, 1, 0).into();
+        let expected = Lambertian::new(c).eval(&r_in, &rec, &wi);
+        assert!((graph.eval(&r_in, &rec, &wi) - expected).len() < 1e-9);
+
+        assert!(ShaderGraph::parse("a = diffuse nothing\noutput a").is_err());
+        assert!(ShaderGraph::parse("a = constant 1\noutput a").is_err());
+        assert!(ShaderGraph::parse("a = diffuse 1\nb = multiply a 1\noutput a").is_err());
+        assert!(ShaderGraph::parse("a = diffuse 1\nb = diffuse a\noutput b").is_err());
+        assert!(ShaderGraph::parse("a = diffuse 1\nb = glossy a 0.5\noutput b").is_err());
+        assert!(ShaderGraph::parse("a = diffuse 1\nb = mix_shader a a a\noutput b").is_err());
+        assert!(ShaderGraph::parse("a = mix_shader 1 1 0.5\noutput a").is_err());
+        assert!(ShaderGraph::parse("a = ramp 0.5 nan 1 1 0\noutput a").is_err());
+        assert!(ShaderGraph::parse("a = diffuse inf\noutput a").is_err());
+
+        // Constant BSDFs are instantiated once, textured ones at each hit.
+        let graph = ShaderGraph::load("tests/data/varnish.graph").unwrap();
+        let compiled = |name: NodeId| graph.compiled[name].is_some();
+        assert!(graph
+            .nodes
+            .iter()
+            .enumerate()
+            .any(|(id, n)| n.is_shader() && compiled(id)));
+        assert!(graph
+            .nodes
+            .iter()
+            .enumerate()
+            .any(|(id, n)| n.is_shader() && !compiled(id)));
+    }
+}
@@ -0,0 +1,280 @@
+//! Analytic daylight after Preetham, Shirley and Smits, "A Practical
+//! Analytic Model for Daylight" (1999), with a sun disk of the true angular
+//! size.
+//!
+//! Luminances are computed in kcd/m² and scaled by an intensity factor,
+//! 0.05 by default, which brings a sunlit white surface near 1.
+use super::*;
+use crate::environment::Environment;
+use crate::light::{Light, LightSample};
+use std::f64::consts::PI;
+
+/// Angular radius of the sun seen from the earth, in radians.
+const SUN_RADIUS: f64 = 0.004_654;
+/// Luminance of the sun above the atmosphere, in kcd/m².
+const SUN_LUMINANCE: f64 = 1.93e6;
+/// Probability of sampling the sun disk rather than the whole sky.
+const SUN_SAMPLING: f64 = 0.5;
+
+/// The sun as a disk light of constant radiance, for use with environments
+/// that don't include it. `Sky` already samples its own.
+pub struct SunDisk {
+    /// Unit direction towards the sun.
+    direction: Point3,
+    cos_radius: f64,
+    radiance: Color,
+}
+
+impl SunDisk {
+    pub fn new(direction: Point3, radiance: Color) -> Self {
+        SunDisk {
+            direction: direction.unit(),
+            cos_radius: SUN_RADIUS.cos(),
+            radiance,
+        }
+    }
+    pub fn solid_angle(&self) -> f64 {
+        2.0 * PI * (1.0 - self.cos_radius)
+    }
+    pub fn contains(&self, direction: &Point3) -> bool {
+        direction.dot(&self.direction) >= self.cos_radius
+    }
+    /// Uniform over the cone subtended by the disk.
+    fn sample_direction(&self) -> Point3 {
+        let cos_theta = 1.0 - random::<f64>() * (1.0 - self.cos_radius);
+        let sin_theta = (1.0 - cos_theta * cos_theta).sqrt();
+        let phi = 2.0 * PI * random::<f64>();
+        let local = Point3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta);
+        Onb::build_from_w(&self.direction).local(&local)
+    }
+}
+
+impl Light for SunDisk {
+    /// A uniform direction in the cone, so the estimate is the radiance
+    /// times the solid angle and shadows get soft penumbras.
+    fn sample(&self, _: &Point3) -> Option<LightSample> {
+        Some(LightSample {
+            wi: self.sample_direction(),
+            distance: f64::INFINITY,
+            radiance: self.solid_angle() * self.radiance,
+            pdf: 0.0,
+        })
+    }
+}
+
+/// Perez et al. luminance distribution coefficients.
+struct Perez([f64; 5]);
+
+impl Perez {
+    /// Relative luminance for a view at `theta` from the zenith and `gamma` from the sun.
+    fn f(&self, theta: f64, gamma: f64) -> f64 {
+        let [a, b, c, d, e] = self.0;
+        let cos_theta = theta.cos().max(0.01);
+        (1.0 + a * (b / cos_theta).exp()) * (1.0 + c * (d * gamma).exp() + e * gamma.cos().powi(2))
+    }
+}
+
+pub struct Sky {
+    sun: SunDisk,
+    /// Zenith angle of the sun, clamped to the horizon.
+    theta_sun: f64,
+    /// Zenith luminance and chromaticity.
+    zenith: [f64; 3],
+    perez: [Perez; 3],
+    /// Lambertian ground radiance seen below the horizon.
+    ground: Color,
+    intensity: f64,
+}
+
+impl Sky {
+    /// `turbidity` ranges from 2 (very clear) to about 10 (hazy).
+    pub fn new(sun_direction: Point3, turbidity: f64, ground_albedo: Color) -> Self {
+        let t = clamp(turbidity, 1.7, 10.0);
+        let sun_direction = sun_direction.unit();
+        let theta_sun = clamp(sun_direction.y, 0.0, 1.0).acos();
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_sun);
+        let luminance = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[f64; 4]; 3]| {
+            let s = [theta_sun.powi(3), theta_sun.powi(2), theta_sun, 1.0];
+            let row = |r: [f64; 4]| r.iter().zip(&s).map(|(a, b)| a * b).sum::<f64>();
+            t * t * row(m[0]) + t * row(m[1]) + row(m[2])
+        };
+        let x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let y = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+        let perez = [
+            Perez([
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ]),
+            Perez([
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ]),
+            Perez([
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ]),
+        ];
+
+        let sun = SunDisk::new(
+            sun_direction,
+            SUN_LUMINANCE * sun_transmittance(theta_sun, t),
+        );
+        let mut sky = Sky {
+            sun,
+            theta_sun,
+            zenith: [luminance.max(0.0), x, y],
+            perez,
+            ground: Color::zeroed(),
+            intensity: 0.05,
+        };
+        if sun_direction.y <= 0.0 {
+            sky.sun.radiance = Color::zeroed();
+        }
+        sky.ground = ground_albedo * sky.irradiance() / PI;
+        sky
+    }
+    pub fn with_intensity(mut self, intensity: f64) -> Self {
+        self.intensity = intensity;
+        self
+    }
+
+    /// Sky dome radiance in kcd/m² for a direction above the horizon, without the sun.
+    fn dome(&self, direction: &Point3) -> Color {
+        let theta = clamp(direction.y, 0.0, 1.0).acos();
+        let gamma = clamp(direction.dot(&self.sun.direction), -1.0, 1.0).acos();
+        let [yz, xz, yz_chroma] = self.zenith;
+        let relative = |p: &Perez| p.f(theta, gamma) / p.f(0.0, self.theta_sun);
+        let luminance = yz * relative(&self.perez[0]);
+        let x = xz * relative(&self.perez[1]);
+        let y = yz_chroma * relative(&self.perez[2]);
+        xyy_to_rgb(x, y, luminance)
+    }
+
+    /// Irradiance on the ground from the dome and the sun, in klx.
+    fn irradiance(&self) -> Color {
+        const STEPS: usize = 32;
+        let mut total = Color::zeroed();
+        for i in 0..STEPS {
+            let theta = (i as f64 + 0.5) / STEPS as f64 * PI / 2.0;
+            for j in 0..4 * STEPS {
+                let phi = (j as f64 + 0.5) / (4 * STEPS) as f64 * 2.0 * PI;
+                let d = Point3::new(
+                    theta.sin() * phi.cos(),
+                    theta.cos(),
+                    theta.sin() * phi.sin(),
+                );
+                let solid_angle =
+                    theta.sin() * (PI / 2.0 / STEPS as f64) * (2.0 * PI / (4 * STEPS) as f64);
+                total += (solid_angle * theta.cos()) * self.dome(&d);
+            }
+        }
+        total + (self.sun.solid_angle() * self.sun.direction.y.max(0.0)) * self.sun.radiance
+    }
+}
+
+/// Rayleigh and aerosol extinction of direct sunlight, evaluated at
+/// representative red, green and blue wavelengths.
+fn sun_transmittance(theta_sun: f64, turbidity: f64) -> Color {
+    let air_mass = 1.0 / (theta_sun.cos() + 0.15 * (93.885 - theta_sun.to_degrees()).powf(-1.253));
+    let beta = 0.04608 * turbidity - 0.04586;
+    let channel = |lambda_um: f64| {
+        let rayleigh = 0.008735 * lambda_um.powf(-4.08);
+        let aerosol = beta * lambda_um.powf(-1.3);
+        (-(rayleigh + aerosol) * air_mass).exp()
+    };
+    Color::new(channel(0.65), channel(0.57), channel(0.475))
+}
+
+/// CIE xyY to linear sRGB.
+fn xyy_to_rgb(x: f64, y: f64, luminance: f64) -> Color {
+    if y <= 0.0 {
+        return Color::zeroed();
+    }
+    let big_x = x / y * luminance;
+    let big_z = (1.0 - x - y) / y * luminance;
+    Color::new(
+        3.2406 * big_x - 1.5372 * luminance - 0.4986 * big_z,
+        -0.9689 * big_x + 1.8758 * luminance + 0.0415 * big_z,
+        0.0557 * big_x - 0.2040 * luminance + 1.0570 * big_z,
+    )
+}
+
+impl Environment for Sky {
+    fn radiance(&self, direction: &Point3) -> Color {
+        let radiance = if direction.y < 0.0 {
+            self.ground
+        } else if self.sun.contains(direction) {
+            self.dome(direction) + self.sun.radiance
+        } else {
+            self.dome(direction)
+        };
+        self.intensity * radiance
+    }
+    /// Picks the sun disk or a uniform direction on the sphere, so the tiny
+    /// but bright sun is found by shadow rays.
+    fn sample(&self) -> Option<(Point3, f64)> {
+        let direction = if self.sun.direction.y > 0.0 && random::<f64>() < SUN_SAMPLING {
+            self.sun.sample_direction()
+        } else {
+            Point3::random_unit()
+        };
+        Some((direction, self.pdf(&direction)))
+    }
+    fn pdf(&self, direction: &Point3) -> f64 {
+        if self.sun.direction.y <= 0.0 {
+            return 1.0 / (4.0 * PI);
+        }
+        let sun = if self.sun.contains(direction) {
+            1.0 / self.sun.solid_angle()
+        } else {
+            0.0
+        };
+        SUN_SAMPLING * sun + (1.0 - SUN_SAMPLING) / (4.0 * PI)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zenith_and_sun() {
+        let sun_direction = Point3::new(0.0, 30f64.to_radians().cos(), 30f64.to_radians().sin());
+        let sky = Sky::new(sun_direction, 2.5, (0.3, 0.3, 0.3).into()).with_intensity(1.0);
+        // At the zenith the model reduces to the zenith luminance, about 8 kcd/m² here.
+        let zenith = sky.radiance(&(0, 1, 0).into());
+        assert!((zenith.luminance() - sky.zenith[0]).abs() < 1e-2 * sky.zenith[0]);
+        assert!(sky.zenith[0] > 7.0 && sky.zenith[0] < 9.0);
+        // Blue sky, and a sun orders of magnitude brighter.
+        assert!(zenith.z > zenith.x);
+        assert!(sky.radiance(&sun_direction).luminance() > 1e5 * zenith.luminance());
+        assert!(sky.radiance(&(0, -1, 0).into()).luminance() > 0.0);
+
+        let (d, pdf) = sky.sample().unwrap();
+        assert!((pdf - sky.pdf(&d)).abs() < 1e-9 * pdf);
+        let disk = SunDisk::new(sun_direction, (1, 1, 1).into());
+        let sample = disk.sample(&Point3::zeroed()).unwrap();
+        assert!(disk.contains(&sample.wi));
+        // The sun covers about 6.8e-5 sr.
+        assert!((disk.solid_angle() - 6.8e-5).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,5 @@
 use super::*;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 pub struct Sphere {
@@ -17,6 +18,22 @@ impl Sphere {
     }
 }
 
+/// `p` is a point on the unit sphere centered at the origin.
+fn get_sphere_uv(p: &Point3, u: &mut f64, v: &mut f64) {
+    let phi = p.z.atan2(p.x);
+    let theta = p.y.asin();
+    *u = 1.0 - (phi + PI) / (2.0 * PI);
+    *v = (theta + PI / 2.0) / PI;
+}
+
+/// Partial derivatives of the surface position along the `get_sphere_uv` parametrization.
+fn get_sphere_dpduv(n: &Point3, radius: f64, dpdu: &mut Point3, dpdv: &mut Point3) {
+    // Clamped to keep a usable frame at the poles.
+    let cos_theta = (n.x * n.x + n.z * n.z).sqrt().max(1e-6);
+    *dpdu = (2.0 * PI * radius) * Point3::new(n.z, 0.0, -n.x);
+    *dpdv = (PI * radius) * Point3::new(-n.y * n.x / cos_theta, cos_theta, -n.y * n.z / cos_theta);
+}
+
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
         let oc = *ray.origin() - self.center;
@@ -32,7 +49,10 @@ impl Hittable for Sphere {
                 rec.p = ray.at(rec.t);
                 let outward_normal = (rec.p - self.center) / self.radius;
                 rec.set_face_normal(ray, &outward_normal);
+                get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                get_sphere_dpduv(&outward_normal, self.radius, &mut rec.dpdu, &mut rec.dpdv);
                 rec.material = Some(Arc::clone(&self.material));
+                rec.primitive = 0;
                 return true;
             }
             let temp = (-half_b + root) / a;
@@ -41,7 +61,10 @@ impl Hittable for Sphere {
                 rec.p = ray.at(rec.t);
                 let outward_normal = (rec.p - self.center) / self.radius;
                 rec.set_face_normal(ray, &outward_normal);
+                get_sphere_uv(&outward_normal, &mut rec.u, &mut rec.v);
+                get_sphere_dpduv(&outward_normal, self.radius, &mut rec.dpdu, &mut rec.dpdv);
                 rec.material = Some(Arc::clone(&self.material));
+                rec.primitive = 0;
                 return true;
             }
         }
@@ -0,0 +1,227 @@
+//! Stereoscopic pairs packed into one image for VR headsets and 3D displays.
+use super::*;
+use crate::camera::{Camera, CameraSample, Degrees, PerspectiveCamera};
+use crate::panorama::{equirectangular, Frame};
+
+/// How the two views share the image.
+#[derive(Clone, Copy)]
+pub enum StereoLayout {
+    /// Left eye on the left half.
+    SideBySide,
+    /// Left eye on the top half.
+    OverUnder,
+}
+
+enum Eye {
+    Left,
+    Right,
+}
+
+impl StereoLayout {
+    /// The eye seeing `film` and the position on that eye's own film.
+    fn split(&self, (s, t): (f64, f64)) -> (Eye, (f64, f64)) {
+        match self {
+            StereoLayout::SideBySide if s < 0.5 => (Eye::Left, (2.0 * s, t)),
+            StereoLayout::SideBySide => (Eye::Right, (2.0 * s - 1.0, t)),
+            StereoLayout::OverUnder if t >= 0.5 => (Eye::Left, (s, 2.0 * t - 1.0)),
+            StereoLayout::OverUnder => (Eye::Right, (s, 2.0 * t)),
+        }
+    }
+    /// Aspect ratio of the packed image for eyes of `eye_aspect_ratio`.
+    pub fn aspect_ratio(&self, eye_aspect_ratio: f64) -> f64 {
+        match self {
+            StereoLayout::SideBySide => 2.0 * eye_aspect_ratio,
+            StereoLayout::OverUnder => eye_aspect_ratio / 2.0,
+        }
+    }
+}
+
+/// Any two cameras rendered into one image.
+pub struct StereoCamera {
+    left: Box<dyn Camera + Send + Sync>,
+    right: Box<dyn Camera + Send + Sync>,
+    layout: StereoLayout,
+}
+
+impl StereoCamera {
+    pub fn new(
+        left: Box<dyn Camera + Send + Sync>,
+        right: Box<dyn Camera + Send + Sync>,
+        layout: StereoLayout,
+    ) -> Self {
+        StereoCamera {
+            left,
+            right,
+            layout,
+        }
+    }
+
+    /// Parallel perspective eyes `ipd` apart, their films shifted so objects
+    /// at the `convergence` distance appear at the screen plane. That avoids
+    /// the vertical parallax toed-in eyes would have.
+    #[allow(clippy::too_many_arguments)]
+    pub fn perspective(
+        lookfrom: Point3,
+        lookat: Point3,
+        vup: Point3,
+        vfov: Degrees,
+        eye_aspect_ratio: f64,
+        ipd: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Self {
+        let theta: f64 = vfov.to_radians().into();
+        let vfov: f64 = vfov.into();
+        // Film width at unit distance.
+        let viewport_width = 2.0 * (theta / 2.0).tan() * eye_aspect_ratio;
+        let right_axis = vup.cross(&(lookfrom - lookat)).unit();
+        let eye = |side: f64| {
+            let offset = side * ipd / 2.0 * right_axis;
+            let camera = PerspectiveCamera::new(
+                lookfrom + offset,
+                lookat + offset,
+                vup,
+                Degrees::new(vfov),
+                eye_aspect_ratio,
+                0.0,
+                convergence,
+            )
+            .with_shift(-side * ipd / 2.0 / (convergence * viewport_width), 0.0);
+            Box::new(camera) as Box<dyn Camera + Send + Sync>
+        };
+        StereoCamera::new(eye(-1.0), eye(1.0), layout)
+    }
+}
+
+impl StereoCamera {
+    fn eye(&self, sample: &CameraSample) -> (&(dyn Camera + Send + Sync), CameraSample) {
+        let (eye, film) = self.layout.split(sample.film);
+        let camera = match eye {
+            Eye::Left => &*self.left,
+            Eye::Right => &*self.right,
+        };
+        (camera, CameraSample { film, ..*sample })
+    }
+}
+
+impl Camera for StereoCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let (camera, sample) = self.eye(sample);
+        camera.generate_ray(&sample)
+    }
+    fn generate_weighted_ray(&self, sample: &CameraSample) -> Option<(Ray, f64)> {
+        let (camera, sample) = self.eye(sample);
+        camera.generate_weighted_ray(&sample)
+    }
+}
+
+/// Omni-directional stereo: a latitude-longitude panorama per eye where
+/// every column is seen from a point on the circle the eyes sweep when
+/// turning the head. The eye separation fades towards the poles, where no
+/// head orientation is preferred.
+pub struct OdsCamera {
+    frame: Frame,
+    ipd: f64,
+    /// Distance at which the rays of both eyes cross, infinite for parallel eyes.
+    convergence: f64,
+    layout: StereoLayout,
+}
+
+impl OdsCamera {
+    pub fn new(
+        center: Point3,
+        lookat: Point3,
+        vup: Point3,
+        ipd: f64,
+        convergence: f64,
+        layout: StereoLayout,
+    ) -> Self {
+        OdsCamera {
+            frame: Frame::new(center, lookat, vup),
+            ipd,
+            convergence,
+            layout,
+        }
+    }
+}
+
+impl Camera for OdsCamera {
+    fn generate_ray(&self, sample: &CameraSample) -> Option<Ray> {
+        let (eye, film) = self.layout.split(sample.film);
+        let side = match eye {
+            Eye::Left => -1.0,
+            Eye::Right => 1.0,
+        };
+        let direction = equirectangular(film);
+        let horizontal = (direction.x * direction.x + direction.z * direction.z).sqrt();
+        // To the right of the horizontal view direction.
+        let right = if horizontal > 0.0 {
+            Point3::new(direction.z, 0.0, -direction.x) / horizontal
+        } else {
+            Point3::zeroed()
+        };
+        let offset = (side * self.ipd / 2.0 * horizontal) * right;
+        let direction = if self.convergence.is_finite() {
+            self.convergence * direction - offset
+        } else {
+            direction
+        };
+        let origin = self.frame.origin + self.frame.to_world(&offset);
+        Some(Ray::new(origin, self.frame.to_world(&direction)).with_time(sample.time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(camera: &dyn Camera, film: (f64, f64)) -> Ray {
+        let sample = CameraSample {
+            film,
+            ..Default::default()
+        };
+        camera.generate_ray(&sample).unwrap()
+    }
+
+    #[test]
+    fn eyes_converge() {
+        let stereo = StereoCamera::perspective(
+            (0, 0, 0).into(),
+            (0, 0, -1).into(),
+            (0, 1, 0).into(),
+            Degrees::new(60.0),
+            1.0,
+            0.064,
+            2.0,
+            StereoLayout::SideBySide,
+        );
+        // The centers of both halves look at the same point 2 away.
+        let left = ray(&stereo, (0.25, 0.5));
+        let right = ray(&stereo, (0.75, 0.5));
+        assert!((left.origin().x + 0.032).abs() < 1e-12);
+        assert!((right.origin().x - 0.032).abs() < 1e-12);
+        for eye in [&left, &right] {
+            let t = -2.0 / eye.direction().z;
+            assert!(eye.at(t).x.abs() < 1e-12);
+        }
+        assert_eq!(StereoLayout::OverUnder.aspect_ratio(2.0), 1.0);
+
+        let ods = OdsCamera::new(
+            Point3::zeroed(),
+            (0, 0, -1).into(),
+            (0, 1, 0).into(),
+            0.064,
+            f64::INFINITY,
+            StereoLayout::OverUnder,
+        );
+        // Looking forward from the top (left) half, the eye sits to the left.
+        let forward = ray(&ods, (0.5, 0.75));
+        assert!((forward.origin().x + 0.032).abs() < 1e-12);
+        assert!((forward.direction().z + 1.0).abs() < 1e-12);
+        // Looking right the eyes sit behind and in front of the center.
+        let right = ray(&ods, (0.75, 0.25));
+        assert!((right.origin().z - 0.032).abs() < 1e-12);
+        // Straight up they merge.
+        assert!(ray(&ods, (0.3, 1.0)).origin().len() < 1e-12);
+    }
+}
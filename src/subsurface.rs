@@ -0,0 +1,41 @@
+//! Subsurface scattering via volumetric random walks.
+//!
+//! The surface itself is a smooth dielectric boundary, light refracted into
+//! the object performs a random walk through the interior `Medium` until it
+//! leaves through the boundary again. The object must be closed.
+use super::*;
+
+pub struct Subsurface {
+    boundary: Dielectric,
+}
+
+impl Subsurface {
+    /// `mean_free_path` is the average distance between scattering events
+    /// per channel, `albedo` the probability of scattering instead of being
+    /// absorbed at each event.
+    pub fn new(ior: f64, mean_free_path: Color, albedo: Color) -> Self {
+        Subsurface {
+            boundary: Dielectric::new(ior)
+                .with_interior(Medium::from_albedo(mean_free_path, albedo)),
+        }
+    }
+    pub fn with_priority(mut self, priority: u32) -> Self {
+        self.boundary = self.boundary.with_priority(priority);
+        self
+    }
+}
+
+impl Material for Subsurface {
+    fn scatter(
+        &self,
+        r_in: &Ray,
+        rec: &mut HitRecord,
+        attenuation: &mut Color,
+        scattered: &mut Ray,
+    ) -> bool {
+        self.boundary.scatter(r_in, rec, attenuation, scattered)
+    }
+    fn medium(&self) -> Option<&Medium> {
+        self.boundary.medium()
+    }
+}
@@ -0,0 +1,258 @@
+use super::*;
+use rand::seq::SliceRandom;
+
+pub trait Texture {
+    fn value(&self, u: f64, v: f64, p: &Point3) -> Color;
+}
+
+pub struct SolidColor {
+    color: Color,
+}
+impl SolidColor {
+    pub fn new(color: Color) -> Self {
+        SolidColor { color }
+    }
+}
+impl Texture for SolidColor {
+    fn value(&self, _: f64, _: f64, _: &Point3) -> Color {
+        self.color
+    }
+}
+
+/// Image looked up with nearest neighbour filtering, values are used as
+/// stored without any gamma decoding.
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    data: Vec<Color>,
+}
+impl ImageTexture {
+    /// `data` is row major, starting at the top left corner, and not empty.
+    pub fn new(width: usize, height: usize, data: Vec<Color>) -> Self {
+        assert!(width > 0 && height > 0, "empty image");
+        assert_eq!(width * height, data.len());
+        ImageTexture {
+            width,
+            height,
+            data,
+        }
+    }
+    pub fn width(&self) -> usize {
+        self.width
+    }
+    pub fn height(&self) -> usize {
+        self.height
+    }
+    /// Loads a binary (`P6`) or ASCII (`P3`) PPM image.
+    pub fn load_ppm(path: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        parse_ppm(&std::fs::read(path)?)
+    }
+}
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _: &Point3) -> Color {
+        let u = clamp(u, 0.0, 1.0);
+        let v = 1.0 - clamp(v, 0.0, 1.0);
+        let i = ((u * self.width as f64) as usize).min(self.width - 1);
+        let j = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.data[j * self.width + i]
+    }
+}
+
+fn invalid(what: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, what)
+}
+
+/// Next whitespace separated PPM header token, skipping comments.
+fn ppm_token(bytes: &[u8], pos: &mut usize) -> std::io::Result<String> {
+    loop {
+        while *pos < bytes.len() && bytes[*pos].is_ascii_whitespace() {
+            *pos += 1;
+        }
+        if *pos < bytes.len() && bytes[*pos] == b'#' {
+            while *pos < bytes.len() && bytes[*pos] != b'\n' {
+                *pos += 1;
+            }
+            continue;
+        }
+        break;
+    }
+    let start = *pos;
+    while *pos < bytes.len() && !bytes[*pos].is_ascii_whitespace() {
+        *pos += 1;
+    }
+    if start == *pos {
+        return Err(invalid("unexpected end of PPM data"));
+    }
+    Ok(String::from_utf8_lossy(&bytes[start..*pos]).into_owned())
+}
+
+fn parse_ppm(bytes: &[u8]) -> std::io::Result<ImageTexture> {
+    let mut pos = 0;
+    let number = |s: String| s.parse::<usize>().map_err(|_| invalid("bad PPM number"));
+    let magic = ppm_token(bytes, &mut pos)?;
+    let width = number(ppm_token(bytes, &mut pos)?)?;
+    let height = number(ppm_token(bytes, &mut pos)?)?;
+    if width == 0 || height == 0 {
+        return Err(invalid("empty PPM image"));
+    }
+    let max = number(ppm_token(bytes, &mut pos)?)?;
+    if max == 0 || max > 65535 {
+        return Err(invalid("bad PPM maximum value"));
+    }
+    let scale = 1.0 / max as f64;
+    let count = width
+        .checked_mul(height)
+        .and_then(|n| n.checked_mul(3))
+        .ok_or_else(|| invalid("PPM image too large"))?;
+    let samples: Vec<f64> = match magic.as_str() {
+        "P3" => (0..count)
+            .map(|_| Ok(number(ppm_token(bytes, &mut pos)?)? as f64 * scale))
+            .collect::<std::io::Result<_>>()?,
+        "P6" => {
+            // A single whitespace byte separates the header from the raster.
+            let raster = bytes.get(pos + 1..).unwrap_or(&[]);
+            let wide = max > 255;
+            let needed = if wide {
+                count.checked_mul(2)
+            } else {
+                Some(count)
+            };
+            let needed = needed.ok_or_else(|| invalid("PPM image too large"))?;
+            if raster.len() < needed {
+                return Err(invalid("truncated PPM raster"));
+            }
+            if wide {
+                raster
+                    .chunks(2)
+                    .take(count)
+                    .map(|b| u16::from_be_bytes([b[0], b[1]]) as f64 * scale)
+                    .collect()
+            } else {
+                raster[..count].iter().map(|b| *b as f64 * scale).collect()
+            }
+        }
+        _ => return Err(invalid("not a P3 or P6 PPM image")),
+    };
+    let data = samples
+        .chunks(3)
+        .map(|c| Color::new(c[0], c[1], c[2]))
+        .collect();
+    Ok(ImageTexture::new(width, height, data))
+}
+
+const PERLIN_POINTS: usize = 256;
+
+/// Gradient noise (Perlin 2002) on a lattice of random unit vectors.
+pub struct Perlin {
+    vectors: Vec<Point3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+impl Perlin {
+    pub fn new() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut perm = || {
+            let mut p: Vec<usize> = (0..PERLIN_POINTS).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let (perm_x, perm_y, perm_z) = (perm(), perm(), perm());
+        Perlin {
+            vectors: (0..PERLIN_POINTS)
+                .map(|_| Point3::random_in_range(-1.0, 1.0).unit())
+                .collect(),
+            perm_x,
+            perm_y,
+            perm_z,
+        }
+    }
+    /// Noise in [-1, 1].
+    pub fn noise(&self, p: &Point3) -> f64 {
+        let (fx, fy, fz) = (p.x.floor(), p.y.floor(), p.z.floor());
+        let (u, v, w) = (p.x - fx, p.y - fy, p.z - fz);
+        let (i, j, k) = (fx as i64, fy as i64, fz as i64);
+        let wrap = |n: i64| (n & (PERLIN_POINTS as i64 - 1)) as usize;
+        // Hermite smoothing of the trilinear weights.
+        let (uu, vv, ww) = (
+            u * u * (3.0 - 2.0 * u),
+            v * v * (3.0 - 2.0 * v),
+            w * w * (3.0 - 2.0 * w),
+        );
+        let mut sum = 0.0;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let g = self.vectors[self.perm_x[wrap(i + di)]
+                        ^ self.perm_y[wrap(j + dj)]
+                        ^ self.perm_z[wrap(k + dk)]];
+                    let (a, b, c) = (di as f64, dj as f64, dk as f64);
+                    let weight = Point3::new(u - a, v - b, w - c);
+                    sum += (a * uu + (1.0 - a) * (1.0 - uu))
+                        * (b * vv + (1.0 - b) * (1.0 - vv))
+                        * (c * ww + (1.0 - c) * (1.0 - ww))
+                        * g.dot(&weight);
+                }
+            }
+        }
+        sum
+    }
+    /// Sum of `depth` octaves of noise.
+    pub fn turbulence(&self, p: &Point3, depth: u32) -> f64 {
+        let mut sum = 0.0;
+        let mut p = *p;
+        let mut weight = 1.0;
+        for _ in 0..depth {
+            sum += weight * self.noise(&p);
+            weight *= 0.5;
+            p *= 2.0;
+        }
+        sum.abs()
+    }
+}
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new()
+    }
+}
+
+/// Gray turbulence in [0, 1] over world space positions.
+pub struct NoiseTexture {
+    noise: Perlin,
+    scale: f64,
+}
+impl NoiseTexture {
+    pub fn new(scale: f64) -> Self {
+        NoiseTexture {
+            noise: Perlin::new(),
+            scale,
+        }
+    }
+}
+impl Texture for NoiseTexture {
+    fn value(&self, _: f64, _: f64, p: &Point3) -> Color {
+        let t = clamp(self.noise.turbulence(&(self.scale * *p), 7), 0.0, 1.0);
+        Color::new(t, t, t)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_ascii_and_binary_ppm() {
+        let ascii = parse_ppm(b"P3\n# comment\n2 1\n255\n255 0 0  0 0 255\n").unwrap();
+        let mut binary = b"P6 2 1 255\n".to_vec();
+        binary.extend_from_slice(&[255, 0, 0, 0, 0, 255]);
+        let binary = parse_ppm(&binary).unwrap();
+        for image in &[ascii, binary] {
+            let p = Point3::zeroed();
+            assert_eq!(image.value(0.1, 0.5, &p).x, 1.0);
+            assert_eq!(image.value(0.9, 0.5, &p).z, 1.0);
+        }
+        assert!(parse_ppm(b"P3\n0 0\n255\n").is_err());
+        assert!(parse_ppm(b"P3 4294967296 4294967296 255\n").is_err());
+        assert!(parse_ppm(b"P6 3074457345618258603 1 65535\n").is_err());
+    }
+}
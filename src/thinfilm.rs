@@ -0,0 +1,255 @@
+//! Thin-film interference (soap bubbles, oil slicks, tempered metal).
+//!
+//! Reflectance of a single film layer between air and a substrate is
+//! evaluated with the Airy summation of the multiply reflected waves.
+use super::*;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+/// Wavelengths in nm at which the red, green and blue channels are evaluated.
+const RGB_WAVELENGTHS: [f64; 3] = [650.0, 532.0, 450.0];
+const SPECTRAL_SAMPLES: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self {
+        Complex { re, im }
+    }
+    fn norm_sqr(self) -> f64 {
+        self.re * self.re + self.im * self.im
+    }
+    fn sqrt(self) -> Self {
+        let r = self.norm_sqr().sqrt().sqrt();
+        let theta = self.im.atan2(self.re) / 2.0;
+        Complex::new(r * theta.cos(), r * theta.sin())
+    }
+    /// `e^(i * phase)`
+    fn cis(phase: f64) -> Self {
+        Complex::new(phase.cos(), phase.sin())
+    }
+}
+impl From<f64> for Complex {
+    fn from(re: f64) -> Self {
+        Complex::new(re, 0.0)
+    }
+}
+impl std::ops::Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+impl std::ops::Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Self) -> Self::Output {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+impl std::ops::Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Self) -> Self::Output {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+impl std::ops::Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Self) -> Self::Output {
+        let d = rhs.norm_sqr();
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+/// Material underneath the film.
+pub enum Substrate {
+    Dielectric(f64),
+    /// Complex index of refraction `eta + i*k` sampled at the RGB wavelengths.
+    Conductor {
+        eta: Color,
+        k: Color,
+    },
+}
+
+impl Substrate {
+    fn ior(&self, wavelength: f64) -> Complex {
+        match self {
+            Substrate::Dielectric(eta) => (*eta).into(),
+            Substrate::Conductor { eta, k } => {
+                Complex::new(interpolate(eta, wavelength), interpolate(k, wavelength))
+            }
+        }
+    }
+}
+
+/// Piecewise linear interpolation of an RGB triple over `RGB_WAVELENGTHS`.
+fn interpolate(c: &Color, wavelength: f64) -> f64 {
+    let [r, g, b] = RGB_WAVELENGTHS;
+    if wavelength >= r {
+        c.x
+    } else if wavelength >= g {
+        c.y + (c.x - c.y) * (wavelength - g) / (r - g)
+    } else if wavelength >= b {
+        c.z + (c.y - c.z) * (wavelength - b) / (g - b)
+    } else {
+        c.z
+    }
+}
+
+/// Analytic fit of the CIE 1931 color matching functions (Wyman et al. 2013).
+fn cie_xyz(wavelength: f64) -> (f64, f64, f64) {
+    let g = |x: f64, mu: f64, s1: f64, s2: f64| {
+        let t = (x - mu) / if x < mu { s1 } else { s2 };
+        (-0.5 * t * t).exp()
+    };
+    let l = wavelength;
+    let x = 1.056 * g(l, 599.8, 37.9, 31.0) + 0.362 * g(l, 442.0, 16.0, 26.7)
+        - 0.065 * g(l, 501.1, 20.4, 26.2);
+    let y = 0.821 * g(l, 568.8, 46.9, 40.5) + 0.286 * g(l, 530.9, 16.3, 31.1);
+    let z = 1.217 * g(l, 437.0, 11.8, 36.0) + 0.681 * g(l, 459.0, 26.0, 13.8);
+    (x, y, z)
+}
+
+fn xyz_to_linear_srgb(x: f64, y: f64, z: f64) -> Color {
+    Color::new(
+        3.2406 * x - 1.5372 * y - 0.4986 * z,
+        -0.9689 * x + 1.8758 * y + 0.0415 * z,
+        0.0557 * x - 0.2040 * y + 1.0570 * z,
+    )
+}
+
+pub enum FilmMode {
+    /// One wavelength per color channel.
+    Rgb,
+    /// Integrates the reflectance spectrum over the visible range.
+    Spectral,
+}
+
+pub struct ThinFilm {
+    /// Film thickness in nm, read from the red channel.
+    thickness: Arc<dyn Texture + Send + Sync>,
+    ior: f64,
+    mode: FilmMode,
+}
+
+impl ThinFilm {
+    /// `thickness` is in nanometers.
+    pub fn new(thickness: f64, ior: f64) -> Self {
+        ThinFilm {
+            thickness: Arc::new(SolidColor::new((thickness, thickness, thickness).into())),
+            ior,
+            mode: FilmMode::Rgb,
+        }
+    }
+    /// Thickness in nanometers is looked up from the red channel of `thickness`.
+    pub fn textured(thickness: Arc<dyn Texture + Send + Sync>, ior: f64) -> Self {
+        ThinFilm {
+            thickness,
+            ior,
+            mode: FilmMode::Rgb,
+        }
+    }
+    pub fn with_mode(mut self, mode: FilmMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Reflectance from air at the incident angle `cos_i` for a single wavelength.
+    fn airy(&self, cos_i: f64, thickness: f64, wavelength: f64, substrate: &Substrate) -> f64 {
+        let n1: Complex = 1.0.into();
+        let n2: Complex = self.ior.into();
+        let n3 = substrate.ior(wavelength);
+        let sin2_i = 1.0 - cos_i * cos_i;
+        let cos_t = |n: Complex| {
+            let sin2: Complex = sin2_i.into();
+            (Complex::from(1.0) - sin2 / (n * n)).sqrt()
+        };
+        let c1: Complex = cos_i.into();
+        let c2 = cos_t(n2);
+        let c3 = cos_t(n3);
+
+        let rs = |na: Complex, ca: Complex, nb: Complex, cb: Complex| {
+            (na * ca - nb * cb) / (na * ca + nb * cb)
+        };
+        let rp = |na: Complex, ca: Complex, nb: Complex, cb: Complex| {
+            (nb * ca - na * cb) / (nb * ca + na * cb)
+        };
+        // Phase difference between successive reflections, complex past the
+        // critical angle where the wave in the film becomes evanescent.
+        let phase = Complex::from(4.0 * PI * thickness / wavelength) * n2 * c2;
+        let e = Complex::cis(phase.re) * Complex::from((-phase.im).exp());
+        let airy = |r12: Complex, r23: Complex| {
+            ((r12 + r23 * e) / (Complex::from(1.0) + r12 * r23 * e)).norm_sqr()
+        };
+        let s = airy(rs(n1, c1, n2, c2), rs(n2, c2, n3, c3));
+        let p = airy(rp(n1, c1, n2, c2), rp(n2, c2, n3, c3));
+        clamp((s + p) / 2.0, 0.0, 1.0)
+    }
+
+    /// RGB reflectance of the film over `substrate` as seen from air.
+    pub fn reflectance(&self, cos_i: f64, substrate: &Substrate, rec: &HitRecord) -> Color {
+        let cos_i = clamp(cos_i, 0.0, 1.0);
+        let thickness = self.thickness.value(rec.u, rec.v, &rec.p).x.max(0.0);
+        match self.mode {
+            FilmMode::Rgb => {
+                let [r, g, b] = RGB_WAVELENGTHS;
+                Color::new(
+                    self.airy(cos_i, thickness, r, substrate),
+                    self.airy(cos_i, thickness, g, substrate),
+                    self.airy(cos_i, thickness, b, substrate),
+                )
+            }
+            FilmMode::Spectral => {
+                let (mut x, mut y, mut z) = (0.0, 0.0, 0.0);
+                let (mut wx, mut wy, mut wz) = (0.0, 0.0, 0.0);
+                for i in 0..SPECTRAL_SAMPLES {
+                    let wavelength = 380.0 + 400.0 * (i as f64 + 0.5) / SPECTRAL_SAMPLES as f64;
+                    let r = self.airy(cos_i, thickness, wavelength, substrate);
+                    let (cx, cy, cz) = cie_xyz(wavelength);
+                    x += r * cx;
+                    y += r * cy;
+                    z += r * cz;
+                    wx += cx;
+                    wy += cy;
+                    wz += cz;
+                }
+                // Normalize so that a flat spectrum maps to white.
+                let white = xyz_to_linear_srgb(wx, wy, wz);
+                let c = xyz_to_linear_srgb(x, y, z) / white;
+                Color::new(
+                    clamp(c.x, 0.0, 1.0),
+                    clamp(c.y, 0.0, 1.0),
+                    clamp(c.z, 0.0, 1.0),
+                )
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vanishing_film_is_plain_fresnel() {
+        let film = ThinFilm::new(0.0, 1.33);
+        let rec = HitRecord::default();
+        for &cos_i in &[1.0, 0.6, 0.1] {
+            let r = film.reflectance(cos_i, &Substrate::Dielectric(1.5), &rec);
+            assert!((r.y - fresnel_dielectric(cos_i, 1.5)).abs() < 1e-6);
+
+            let (eta, k) = ((0.2, 0.9, 1.1).into(), (3.9, 2.4, 2.1).into());
+            let r = film.reflectance(cos_i, &Substrate::Conductor { eta, k }, &rec);
+            assert!((r.x - fresnel_conductor(cos_i, eta, k).x).abs() < 1e-6);
+        }
+    }
+}
@@ -19,3 +19,9 @@ impl<T: Hittable> Hittable for Vec<T> {
         hit_anything
     }
 }
+
+impl<T: Hittable + ?Sized> Hittable for Box<T> {
+    fn hit(&self, ray: &Ray, t_min: f64, t_max: f64, rec: &mut HitRecord) -> bool {
+        (**self).hit(ray, t_min, t_max, rec)
+    }
+}
@@ -228,7 +228,7 @@ where
         Vec3 {
             x: r * a.cos(),
             y: r * a.sin(),
-            z: z,
+            z,
             marker: PhantomData,
         }
     }
@@ -282,11 +282,20 @@ impl Vec3<Color> {
     pub fn as_point3(self) -> Vec3<Point3> {
         unsafe { std::mem::transmute(self) }
     }
+    /// Rec. 709 relative luminance.
+    pub fn luminance(&self) -> f64 {
+        0.2126 * self.x + 0.7152 * self.y + 0.0722 * self.z
+    }
 }
 impl Vec3<Point3> {
     pub fn as_color(self) -> Vec3<Color> {
         unsafe { std::mem::transmute(self) }
     }
+    /// Rotates around the unit `axis` by `angle` radians (Rodrigues).
+    pub fn rotate(&self, axis: &Vec3<Point3>, angle: f64) -> Vec3<Point3> {
+        let (sin, cos) = angle.sin_cos();
+        cos * *self + (axis.dot(self) * (1.0 - cos)) * *axis + sin * axis.cross(self)
+    }
 }
 
 impl<T> std::fmt::Display for Vec3<T> {
@@ -307,13 +316,13 @@ impl ExpressibleInThree for Point3 {}
 #[cfg(test)]
 mod tests {
     use super::*;
-    // #[test]
-    // fn dot() {
-    //     let v = Vec3<Point3>::new(-6., 8., 0.);
-    //     let normal = Point3::new(5., 12., 0.);
-    //     let product = v.dot(&normal);
-    //     assert_eq!(product, 66.0);
-    // }
+    #[test]
+    fn dot() {
+        let v = Vec3::<Point3>::new(-6., 8., 0.);
+        let normal = Vec3::<Point3>::new(5., 12., 0.);
+        let product = v.dot(&normal);
+        assert_eq!(product, 66.0);
+    }
 
     // #[test]
     // fn refract() {